@@ -0,0 +1,439 @@
+//! Ledger export of the payments recorded in the history of the multisig.
+//!
+//! Each posting is booked twice, debited to one account and credited to another, so the legs
+//! sum to zero per token. Transfers go from the multisig to their receiver. Escrow transfers go
+//! from the multisig to its escrow account when the request is executed, then from the escrow
+//! account to the receiver when claimed, as read from the escrow events.
+
+use std::collections::HashMap;
+use std::fs;
+
+use chrono::{DateTime, Datelike, Utc};
+use models::*;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json;
+use near_sdk::AccountId;
+
+use crate::Options;
+
+/// Decimals of NEAR amounts, in yoctoNEAR.
+const NEAR_DECIMALS: u8 = 24;
+
+/// Suffix of the clearing account holding the escrowed funds of the multisig.
+const ESCROW_ACCOUNT_SUFFIX: &str = ":escrow";
+
+const CSV_HEADER: [&str; 11] = [
+    "date",
+    "request_id",
+    "payment_id",
+    "token",
+    "amount",
+    "account",
+    "receiver",
+    "label",
+    "invoice_number",
+    "category",
+    "status",
+];
+
+/// One leg of a ledger entry.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LedgerRow {
+    /// Time the request was executed, or the escrow payment claimed, in UTC.
+    pub date: String,
+    pub request_id: RequestId,
+    pub payment_id: Option<String>,
+    /// `NEAR` or the token account.
+    pub token: String,
+    /// Amount with the decimals of the token, negative for the credited leg.
+    pub amount: String,
+    /// Account of the leg: the receiver of the payment, the multisig or its escrow account.
+    pub account: String,
+    pub receiver: AccountId,
+    pub label: String,
+    pub invoice_number: String,
+    pub category: String,
+    /// `executed`, `pending` while waiting for the result of the request,
+    /// `claimed` for escrow payments.
+    pub status: String,
+}
+
+/// Event of the multisig with the timestamp of its block, as recorded by an indexer.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecordedEvent {
+    pub block_timestamp: U64,
+    /// Log line of the event, `EVENT_JSON:{...}`.
+    pub log: String,
+}
+
+/// A payment made by a request.
+#[derive(Clone)]
+struct Payment {
+    token_id: Option<AccountId>,
+    amount: u128,
+    receiver_id: AccountId,
+    metadata: PaymentMetadata,
+    is_escrow: bool,
+    payment_id: Option<Base58CryptoHash>,
+}
+
+/// Movement of a payment between two accounts.
+struct Posting {
+    timestamp: u64,
+    request_id: RequestId,
+    payment: Payment,
+    status: &'static str,
+    debit: String,
+    credit: String,
+}
+
+/// Runs `ledger`: reads the recorded history and token registry and prints the ledger.
+pub fn run(options: &Options) -> Result<String, String> {
+    let multisig = options.account_id("multisig")?;
+    let history: Vec<HistoryEntry> = read_json(options.value("history")?)?;
+    let events: Vec<RecordedEvent> = match options.values.get("events") {
+        Some(path) => read_json(path)?,
+        None => vec![],
+    };
+    let tokens: Vec<(AccountId, Option<TokenMetadata>)> = match options.values.get("tokens") {
+        Some(path) => read_json(path)?,
+        None => vec![],
+    };
+    let month = options
+        .values
+        .get("month")
+        .map(|month| parse_month(month))
+        .transpose()?;
+
+    let rows = ledger(
+        &history,
+        &events,
+        &token_decimals(&tokens),
+        &multisig,
+        month,
+    )?;
+    match options.values.get("format").map(String::as_str) {
+        None | Some("csv") => Ok(to_csv(&rows)),
+        Some("json") => {
+            Ok(serde_json::to_string_pretty(&rows).expect("ledger rows are serializable"))
+        }
+        Some(format) => Err(format!("unknown format: {format}")),
+    }
+}
+
+/// Returns the decimals of the tokens with known metadata, from the `get_registered_tokens` view.
+pub fn token_decimals(tokens: &[(AccountId, Option<TokenMetadata>)]) -> HashMap<AccountId, u8> {
+    tokens
+        .iter()
+        .filter_map(|(token_id, metadata)| {
+            metadata
+                .as_ref()
+                .map(|metadata| (token_id.clone(), metadata.decimals))
+        })
+        .collect()
+}
+
+/// Builds the ledger of the payments in given history and of the escrow payments claimed in
+/// given events, optionally restricted to a month.
+/// Deleted and failed requests moved no funds and are left out.
+pub fn ledger(
+    history: &[HistoryEntry],
+    events: &[RecordedEvent],
+    decimals: &HashMap<AccountId, u8>,
+    multisig: &AccountId,
+    month: Option<(i32, u32)>,
+) -> Result<Vec<LedgerRow>, String> {
+    let escrow_account = format!("{multisig}{ESCROW_ACCOUNT_SUFFIX}");
+    let mut postings = vec![];
+    // escrow payments by id, with the request that created them
+    let mut escrows: HashMap<String, (RequestId, Payment)> = HashMap::new();
+    for entry in history {
+        let status = match entry.status {
+            RequestStatus::Executing => "pending",
+            RequestStatus::Executed { success: true } => "executed",
+            RequestStatus::Executed { success: false } | RequestStatus::Deleted { .. } => continue,
+        };
+        for payment in payments(entry) {
+            let debit = if payment.is_escrow {
+                escrow_account.clone()
+            } else {
+                payment.receiver_id.to_string()
+            };
+            if let Some(payment_id) = &payment.payment_id {
+                escrows.insert(payment_id.into(), (entry.request_id, payment.clone()));
+            }
+            postings.push(Posting {
+                timestamp: entry.finished_at.0,
+                request_id: entry.request_id,
+                payment,
+                status,
+                debit,
+                credit: multisig.to_string(),
+            });
+        }
+    }
+    for event in events {
+        let payment_id = match EventLog::from_log(&event.log).map(|log| log.event) {
+            Some(PaymentsEvent::EscrowClaimed { payment_id, .. }) => payment_id,
+            _ => continue,
+        };
+        let payment_id = String::from(&payment_id);
+        let (request_id, payment) = escrows
+            .get(&payment_id)
+            .ok_or_else(|| format!("escrow payment {payment_id} is not in the history"))?;
+        postings.push(Posting {
+            timestamp: event.block_timestamp.0,
+            request_id: *request_id,
+            payment: payment.clone(),
+            status: "claimed",
+            debit: payment.receiver_id.to_string(),
+            credit: escrow_account.clone(),
+        });
+    }
+    postings.sort_by_key(|posting| posting.timestamp);
+
+    let mut rows = vec![];
+    for posting in postings {
+        let date = to_datetime(posting.timestamp)?;
+        if month.is_some_and(|(year, month)| date.year() != year || date.month() != month) {
+            continue;
+        }
+        let payment = posting.payment;
+        let decimals = match &payment.token_id {
+            None => NEAR_DECIMALS,
+            Some(token_id) => *decimals
+                .get(token_id)
+                .ok_or_else(|| format!("missing metadata of token {token_id}"))?,
+        };
+        let token = payment
+            .token_id
+            .as_ref()
+            .map_or("NEAR".to_string(), ToString::to_string);
+        let payment_id = payment.payment_id.as_ref().map(String::from);
+        for (account, is_credit) in [(posting.debit, false), (posting.credit, true)] {
+            rows.push(LedgerRow {
+                date: date.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                request_id: posting.request_id,
+                payment_id: payment_id.clone(),
+                token: token.clone(),
+                amount: format_amount(payment.amount, decimals, is_credit),
+                account,
+                receiver: payment.receiver_id.clone(),
+                label: payment.metadata.label.clone().unwrap_or_default(),
+                invoice_number: payment.metadata.invoice_number.clone().unwrap_or_default(),
+                category: payment.metadata.category.clone().unwrap_or_default(),
+                status: posting.status.to_string(),
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// Writes given rows as CSV, with a header line.
+pub fn to_csv(rows: &[LedgerRow]) -> String {
+    let mut csv = CSV_HEADER.join(",") + "\n";
+    for row in rows {
+        let fields = [
+            row.date.clone(),
+            row.request_id.to_string(),
+            row.payment_id.clone().unwrap_or_default(),
+            row.token.clone(),
+            row.amount.clone(),
+            row.account.clone(),
+            row.receiver.to_string(),
+            row.label.clone(),
+            row.invoice_number.clone(),
+            row.category.clone(),
+            row.status.clone(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv += &(fields.join(",") + "\n");
+    }
+    csv
+}
+
+/// Returns the payments of given request, matched with the escrow payments it created.
+fn payments(entry: &HistoryEntry) -> Vec<Payment> {
+    let mut payment_ids = entry.payment_ids.iter();
+    entry
+        .request
+        .actions
+        .iter()
+        .filter_map(|action| {
+            let payment = action.payment(&entry.request.receiver_id)?;
+            let is_escrow = matches!(
+                action.kind(),
+                ActionKind::NearEscrowTransfer | ActionKind::FTEscrowTransfer
+            );
+            let payment_id = if is_escrow {
+                payment_ids.next().copied()
+            } else {
+                None
+            };
+            Some(Payment {
+                token_id: payment.token_id.clone(),
+                amount: payment.amount.0,
+                receiver_id: payment.receiver_id.clone(),
+                metadata: payment.metadata.clone(),
+                is_escrow,
+                payment_id,
+            })
+        })
+        .collect()
+}
+
+/// Formats a raw amount with given decimals, keeping all of them.
+fn format_amount(amount: u128, decimals: u8, negative: bool) -> String {
+    let digits = format!("{amount:0>width$}", width = decimals as usize + 1);
+    let (units, fraction) = digits.split_at(digits.len() - decimals as usize);
+    let sign = if negative { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{sign}{units}")
+    } else {
+        format!("{sign}{units}.{fraction}")
+    }
+}
+
+fn to_datetime(timestamp: u64) -> Result<DateTime<Utc>, String> {
+    DateTime::from_timestamp(
+        (timestamp / 1_000_000_000) as i64,
+        (timestamp % 1_000_000_000) as u32,
+    )
+    .ok_or_else(|| format!("invalid timestamp: {timestamp}"))
+}
+
+/// Parses a month as `YYYY-MM`.
+fn parse_month(month: &str) -> Result<(i32, u32), String> {
+    month
+        .split_once('-')
+        .and_then(|(year, month)| Some((year.parse().ok()?, month.parse().ok()?)))
+        .filter(|(_, month)| (1..=12).contains(month))
+        .ok_or_else(|| format!("invalid --month {month}, expected YYYY-MM"))
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn read_json<T: near_sdk::serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+    serde_json::from_str(&content).map_err(|e| format!("failed to parse {path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = include_str!("../tests/fixtures/history.json");
+    const EVENTS: &str = include_str!("../tests/fixtures/events.json");
+    const TOKENS: &str = include_str!("../tests/fixtures/tokens.json");
+    const LEDGER_CSV: &str = include_str!("../tests/fixtures/ledger_2024_03.csv");
+
+    fn fixture_ledger(month: Option<(i32, u32)>) -> Vec<LedgerRow> {
+        let history: Vec<HistoryEntry> = serde_json::from_str(HISTORY).unwrap();
+        let events: Vec<RecordedEvent> = serde_json::from_str(EVENTS).unwrap();
+        let tokens: Vec<(AccountId, Option<TokenMetadata>)> = serde_json::from_str(TOKENS).unwrap();
+        ledger(
+            &history,
+            &events,
+            &token_decimals(&tokens),
+            &"multisig.near".parse().unwrap(),
+            month,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ledger_csv() {
+        assert_eq!(to_csv(&fixture_ledger(Some((2024, 3)))), LEDGER_CSV);
+    }
+
+    #[test]
+    fn test_ledger_json() {
+        let rows = fixture_ledger(None);
+        // the failed transfer of April and the deleted request are left out
+        assert_eq!(rows.len(), 10);
+        assert!(rows.iter().all(|row| row.request_id != 5));
+        // the unclaimed escrow payment stays in the escrow account
+        let json: serde_json::Value = serde_json::to_value(&rows[8..]).unwrap();
+        let leg = |account: &str, amount: &str| {
+            serde_json::json!({
+                "date": "2024-04-03T09:00:00Z",
+                "request_id": 6,
+                "payment_id": "GM3SGmhCkH1JqeraNt4b7QexETCjcd5KkefEKzS6yyGc",
+                "token": "usdc.near",
+                "amount": amount,
+                "account": account,
+                "receiver": "dave.near",
+                "label": "design review",
+                "invoice_number": "INV-0051",
+                "category": "engineering",
+                "status": "executed",
+            })
+        };
+        assert_eq!(
+            json,
+            serde_json::json!([
+                leg("multisig.near:escrow", "0.750000"),
+                leg("multisig.near", "-0.750000"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unknown_escrow_payment() {
+        let events: Vec<RecordedEvent> = serde_json::from_str(EVENTS).unwrap();
+        let tokens: Vec<(AccountId, Option<TokenMetadata>)> = serde_json::from_str(TOKENS).unwrap();
+        assert_eq!(
+            ledger(
+                &[],
+                &events,
+                &token_decimals(&tokens),
+                &"multisig.near".parse().unwrap(),
+                None
+            ),
+            Err(
+                "escrow payment US517G5965aydkZ46HS38QLi7UQiSojurfbQfKCELFx is not in the history"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_missing_token_metadata() {
+        let history: Vec<HistoryEntry> = serde_json::from_str(HISTORY).unwrap();
+        assert_eq!(
+            ledger(
+                &history,
+                &[],
+                &HashMap::new(),
+                &"multisig.near".parse().unwrap(),
+                None
+            ),
+            Err("missing metadata of token usdc.near".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(1_500_000, 6, false), "1.500000");
+        assert_eq!(format_amount(5, 6, true), "-0.000005");
+        assert_eq!(format_amount(42, 0, false), "42");
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(parse_month("2024-03"), Ok((2024, 3)));
+        assert!(parse_month("2024-13").is_err());
+        assert!(parse_month("march").is_err());
+    }
+}
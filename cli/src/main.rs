@@ -0,0 +1,359 @@
+//! Builds multisig requests offline and prints the arguments of `add_request_and_confirm`,
+//! or the whole function call for an external signer.
+//! Also exports the payment history of a multisig as a ledger.
+
+mod ledger;
+
+use std::collections::{HashMap, HashSet};
+use std::process::exit;
+
+use chrono::NaiveDate;
+use models::*;
+use near_payments_client::{FunctionCall, RequestBuilder};
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde_json::{self, json};
+use near_sdk::{base64, AccountId, PublicKey};
+
+const USAGE: &str = "\
+Usage: near-payments-cli <command> [options]
+
+Commands:
+  transfer           --receiver <account> --amount <yocto> [metadata]
+  escrow             --multisig <account> --receiver <account> --amount <yocto> [metadata]
+  ft-escrow          --multisig <account> --token <account> --receiver <account> --amount <units> [metadata]
+  add-member         --multisig <account> (--account <account> | --public-key <key>)
+  set-confirmations  --multisig <account> --num-confirmations <n>
+  set-budget         --multisig <account> --category <category> [--token <account>]
+                     (--amount <units> --period month|quarter|year | --remove)
+  ledger             --multisig <account> --history <file> [--events <file>] [--tokens <file>]
+                     [--month YYYY-MM] [--format csv|json]
+                     exports the output of get_history, with token decimals from get_registered_tokens;
+                     escrow payments are held in <multisig>:escrow until claimed, as read from the
+                     events, a JSON list of block_timestamp and log line
+
+Payment metadata, the category is required:
+  --category <category> --label <text> --invoice <number> --tags <tag,tag>
+  --due-date YYYY-MM-DD --reference <external reference>
+
+Options:
+  --not-cancellable  escrow payments can't be cancelled
+  --no-confirm       call add_request instead of add_request_and_confirm
+  --remove           remove the budget
+  --borsh            print the Borsh encoded function call in base64 instead of the JSON args";
+
+/// Options without a value.
+const FLAGS: [&str; 4] = ["not-cancellable", "no-confirm", "remove", "borsh"];
+
+#[derive(Debug, Default)]
+struct Options {
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument: {arg}"))?;
+            if FLAGS.contains(&name) {
+                options.flags.insert(name.to_string());
+            } else {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for --{name}"))?;
+                options.values.insert(name.to_string(), value.clone());
+            }
+        }
+        Ok(options)
+    }
+
+    fn value(&self, name: &str) -> Result<&str, String> {
+        self.values
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing --{name}"))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    fn account_id(&self, name: &str) -> Result<AccountId, String> {
+        let value = self.value(name)?;
+        value
+            .parse()
+            .map_err(|e| format!("invalid account id for --{name} {value}: {e}"))
+    }
+
+    /// Parses an amount in raw units, the way the contract reads `U128`.
+    fn amount(&self, name: &str) -> Result<u128, String> {
+        let value = self.value(name)?;
+        let amount: U128 = serde_json::from_value(json!(value))
+            .map_err(|_| format!("invalid amount for --{name}: {value}"))?;
+        if amount.0 == 0 {
+            return Err(format!("--{name} must be greater than zero"));
+        }
+        Ok(amount.0)
+    }
+
+    /// Reads the payment metadata options, all optional but the category.
+    fn metadata(&self) -> Result<PaymentMetadata, String> {
+        let due_date = match self.values.get("due-date") {
+            Some(date) => {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid --due-date {date}, expected YYYY-MM-DD"))?;
+                let timestamp = date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+                Some(U64(timestamp as u64 * 1_000_000_000))
+            }
+            None => None,
+        };
+        Ok(PaymentMetadata {
+            label: self.values.get("label").cloned(),
+            invoice_number: self.values.get("invoice").cloned(),
+            category: Some(self.value("category")?.to_string()),
+            tags: self
+                .values
+                .get("tags")
+                .map(|tags| tags.split(',').map(|tag| tag.trim().to_string()).collect())
+                .unwrap_or_default(),
+            due_date,
+            external_reference: self.values.get("reference").cloned(),
+        })
+    }
+
+    fn member(&self) -> Result<MultisigMember, String> {
+        match (self.values.get("account"), self.values.get("public-key")) {
+            (Some(_), None) => Ok(MultisigMember::Account {
+                account_id: self.account_id("account")?,
+            }),
+            (None, Some(key)) => Ok(MultisigMember::AccessKey {
+                public_key: key
+                    .parse::<PublicKey>()
+                    .map_err(|e| format!("invalid public key {key}: {e:?}"))?,
+            }),
+            _ => Err("expected one of --account or --public-key".to_string()),
+        }
+    }
+}
+
+fn build_request(command: &str, options: &Options) -> Result<MultiSigRequest, String> {
+    let is_cancellable = !options.flag("not-cancellable");
+    let builder = match command {
+        "transfer" => RequestBuilder::new(options.account_id("receiver")?)
+            .transfer(options.amount("amount")?, options.metadata()?),
+        "escrow" => RequestBuilder::new(options.account_id("multisig")?).near_escrow(
+            options.account_id("receiver")?,
+            options.amount("amount")?,
+            options.metadata()?,
+            is_cancellable,
+        ),
+        "ft-escrow" => RequestBuilder::new(options.account_id("multisig")?).ft_escrow(
+            options.account_id("token")?,
+            options.account_id("receiver")?,
+            options.amount("amount")?,
+            options.metadata()?,
+            is_cancellable,
+        ),
+        "add-member" => {
+            RequestBuilder::new(options.account_id("multisig")?).add_member(options.member()?)
+        }
+        "set-confirmations" => {
+            let value = options.value("num-confirmations")?;
+            let num_confirmations = value
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("invalid --num-confirmations: {value}"))?;
+            RequestBuilder::new(options.account_id("multisig")?)
+                .set_num_confirmations(num_confirmations)
+        }
+        "set-budget" => {
+            let token_id = match options.values.get("token") {
+                Some(_) => Some(options.account_id("token")?),
+                None => None,
+            };
+            let budget = if options.flag("remove") {
+                None
+            } else {
+                let period = match options.value("period")? {
+                    "month" => BudgetPeriod::Month,
+                    "quarter" => BudgetPeriod::Quarter,
+                    "year" => BudgetPeriod::Year,
+                    period => return Err(format!("invalid --period: {period}")),
+                };
+                Some(Budget {
+                    amount: options.amount("amount")?.into(),
+                    period,
+                })
+            };
+            RequestBuilder::new(options.account_id("multisig")?).set_budget(
+                options.value("category")?,
+                token_id,
+                budget,
+            )
+        }
+        _ => return Err(format!("unknown command: {command}")),
+    };
+    Ok(builder.build())
+}
+
+fn run(args: &[String]) -> Result<String, String> {
+    let (command, args) = args
+        .split_first()
+        .ok_or_else(|| "missing command".to_string())?;
+    let options = Options::parse(args)?;
+    if command == "ledger" {
+        return ledger::run(&options);
+    }
+    let request = build_request(command, &options)?;
+    let method_name = if options.flag("no-confirm") {
+        "add_request"
+    } else {
+        "add_request_and_confirm"
+    };
+    let call = FunctionCall::json(method_name, &json!({ "request": request }));
+    if options.flag("borsh") {
+        Ok(base64::encode(
+            call.try_to_vec().expect("call is serializable"),
+        ))
+    } else {
+        Ok(String::from_utf8(call.args).expect("JSON is UTF-8"))
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => println!("{output}"),
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_payments_client::DEFAULT_GAS;
+    use near_sdk::borsh::BorshDeserialize;
+
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_escrow_json() {
+        let output = run(&args(
+            "escrow --multisig multisig.near --receiver bob.near --amount 1000 --label march --category payroll --tags q1,contractors --due-date 2024-03-31 --not-cancellable",
+        ))
+        .unwrap();
+        let request: MultiSigRequest = serde_json::from_value(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap()["request"].clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            MultiSigRequest {
+                receiver_id: "multisig.near".parse().unwrap(),
+                actions: vec![MultiSigRequestAction::NearEscrowTransfer {
+                    receiver_id: "bob.near".parse().unwrap(),
+                    amount: U128(1000),
+                    metadata: PaymentMetadata {
+                        label: Some("march".to_string()),
+                        category: Some("payroll".to_string()),
+                        tags: vec!["q1".to_string(), "contractors".to_string()],
+                        due_date: Some(U64(1_711_843_200_000_000_000)),
+                        ..Default::default()
+                    },
+                    is_cancellable: false,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_borsh_function_call() {
+        let output = run(&args(
+            "set-confirmations --multisig multisig.near --num-confirmations 2 --no-confirm --borsh",
+        ))
+        .unwrap();
+        let call = FunctionCall::try_from_slice(&base64::decode(output).unwrap()).unwrap();
+        assert_eq!(call.method_name, "add_request");
+        assert_eq!(call.gas, DEFAULT_GAS);
+        assert_eq!(call.deposit, 0);
+        let args: serde_json::Value = serde_json::from_slice(&call.args).unwrap();
+        assert_eq!(
+            args["request"]["actions"][0],
+            json!({ "type": "SetNumConfirmations", "num_confirmations": 2 })
+        );
+    }
+
+    #[test]
+    fn test_add_member() {
+        let output = run(&args(
+            "add-member --multisig multisig.near --public-key ed25519:Eg2jtsiMrprn7zgKKUk79qM1hWhANsFyE6JSX4txLEuy",
+        ))
+        .unwrap();
+        assert!(output.contains("AddMember"));
+        assert!(run(&args("add-member --multisig multisig.near")).is_err());
+    }
+
+    #[test]
+    fn test_set_budget() {
+        let output = run(&args(
+            "set-budget --multisig multisig.near --category payroll --token usdc.near --amount 5000 --period quarter",
+        ))
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap()["request"]["actions"][0],
+            json!({
+                "type": "SetBudget",
+                "category": "payroll",
+                "token_id": "usdc.near",
+                "budget": { "amount": "5000", "period": "quarter" },
+            })
+        );
+        let output = run(&args(
+            "set-budget --multisig multisig.near --category payroll --remove",
+        ))
+        .unwrap();
+        assert!(output.contains(r#""budget":null"#));
+        assert!(run(&args(
+            "set-budget --multisig multisig.near --category payroll --amount 1 --period week"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(
+            run(&args("transfer --receiver bob.near")),
+            Err("missing --amount".to_string())
+        );
+        assert!(run(&args("transfer --receiver Bob! --amount 1")).is_err());
+        assert!(run(&args("transfer --receiver bob.near --amount 1.5")).is_err());
+        assert!(run(&args("transfer --receiver bob.near --amount 0")).is_err());
+        assert_eq!(
+            run(&args("transfer --receiver bob.near --amount 1")),
+            Err("missing --category".to_string())
+        );
+        assert!(run(&args(
+            "transfer --receiver bob.near --amount 1 --category ops --due-date 31/03/2024"
+        ))
+        .is_err());
+        assert!(run(&args(
+            "set-confirmations --multisig m.near --num-confirmations 0"
+        ))
+        .is_err());
+        assert_eq!(
+            run(&args("pay --receiver bob.near")),
+            Err("unknown command: pay".to_string())
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+use models::ContractError;
+use near_sdk::serde_json;
+
+/// Prefix of the failure message of a contract panic, as returned by the RPC.
+const PANIC_PREFIX: &str = "Smart contract panicked: ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientError {
+    /// The RPC request failed or the transaction could not be sent.
+    Transport(String),
+    /// The call was executed and failed, with the failure message returned by the RPC.
+    Execution(String),
+    /// The result of the call could not be parsed.
+    InvalidResponse(String),
+}
+
+impl ClientError {
+    /// Returns the `ContractError` raised by the contract, `None` for other failures.
+    pub fn contract_error(&self) -> Option<ContractError> {
+        match self {
+            ClientError::Execution(message) => {
+                let message = message
+                    .find(PANIC_PREFIX)
+                    .map_or(message.as_str(), |i| &message[i + PANIC_PREFIX.len()..]);
+                serde_json::from_str(message).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Transport(e) => write!(f, "transport error: {e}"),
+            ClientError::Execution(e) => write!(f, "execution failed: {e}"),
+            ClientError::InvalidResponse(e) => write!(f, "invalid response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
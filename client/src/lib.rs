@@ -0,0 +1,589 @@
+//! Typed client for the payments contract.
+//!
+//! `PaymentsClient` exposes the change and view methods of the contract with the `models` types,
+//! on top of a `Transport` provided by the caller, or the JSON-RPC transport of the `rpc` feature.
+
+use models::*;
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::serde::de::DeserializeOwned;
+use near_sdk::serde_json::{self, json, Value};
+use near_sdk::{AccountId, CryptoHash, ONE_YOCTO};
+
+pub mod error;
+pub mod requests;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod transport;
+
+pub use error::ClientError;
+pub use requests::RequestBuilder;
+#[cfg(feature = "rpc")]
+pub use rpc::JsonRpcTransport;
+pub use transport::{FunctionCall, Transport, DEFAULT_GAS};
+
+pub struct PaymentsClient<T> {
+    transport: T,
+    contract_id: AccountId,
+}
+
+impl<T: Transport> PaymentsClient<T> {
+    pub fn new(transport: T, contract_id: AccountId) -> Self {
+        Self {
+            transport,
+            contract_id,
+        }
+    }
+
+    pub fn contract_id(&self) -> &AccountId {
+        &self.contract_id
+    }
+
+    /// Starts a request on the multisig itself, used for configuration and escrow actions.
+    pub fn request(&self) -> RequestBuilder {
+        RequestBuilder::new(self.contract_id.clone())
+    }
+
+    /********************************
+    Change methods
+    ********************************/
+
+    /// Initializes the multisig with given members and number of confirmations.
+    pub async fn init(
+        &self,
+        members: Vec<MultisigMember>,
+        num_confirmations: u32,
+    ) -> Result<(), ClientError> {
+        self.call(
+            "new",
+            json!({ "members": members, "num_confirmations": num_confirmations }),
+        )
+        .await
+    }
+
+    pub async fn add_request(
+        &self,
+        request: MultiSigRequest,
+    ) -> Result<MultiSigResponse, ClientError> {
+        self.call("add_request", json!({ "request": request }))
+            .await
+    }
+
+    /// Adds a request and confirms it. Returns the outcome of the request if this executed it.
+    pub async fn add_request_and_confirm(
+        &self,
+        request: MultiSigRequest,
+    ) -> Result<MultiSigResponse, ClientError> {
+        self.call("add_request_and_confirm", json!({ "request": request }))
+            .await
+    }
+
+    pub async fn delete_request(&self, request_id: RequestId) -> Result<(), ClientError> {
+        self.call("delete_request", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn delete_expired_request(&self, request_id: RequestId) -> Result<(), ClientError> {
+        self.call(
+            "delete_expired_request",
+            json!({ "request_id": request_id }),
+        )
+        .await
+    }
+
+    /// Confirms given request. Returns the outcome of the request if this executed it.
+    pub async fn confirm(&self, request_id: RequestId) -> Result<MultiSigResponse, ClientError> {
+        self.call("confirm", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn revoke_confirmation(&self, request_id: RequestId) -> Result<(), ClientError> {
+        self.call("revoke_confirmation", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn reject(&self, request_id: RequestId) -> Result<(), ClientError> {
+        self.call("reject", json!({ "request_id": request_id }))
+            .await
+    }
+
+    /// Executes given queued request once its timelock has passed.
+    pub async fn execute(&self, request_id: RequestId) -> Result<MultiSigResponse, ClientError> {
+        self.call("execute", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn veto(&self, request_id: RequestId) -> Result<(), ClientError> {
+        self.call("veto", json!({ "request_id": request_id })).await
+    }
+
+    pub async fn refresh_token_balances(&self) -> Result<(), ClientError> {
+        self.call("refresh_token_balances", json!({})).await
+    }
+
+    pub async fn reconcile_tokens(&self) -> Result<(), ClientError> {
+        self.call("reconcile_tokens", json!({})).await
+    }
+
+    /// Claims given escrow payment, must be sent by its receiver.
+    pub async fn claim_payment(&self, payment_id: Base58CryptoHash) -> Result<(), ClientError> {
+        let call = FunctionCall::json("claim_payment", &json!({ "payment_id": payment_id }))
+            .with_deposit(ONE_YOCTO);
+        self.transport.call(&self.contract_id, call).await?;
+        Ok(())
+    }
+
+    /********************************
+    View methods
+    ********************************/
+
+    pub async fn get_members(&self) -> Result<Vec<MultisigMember>, ClientError> {
+        self.view("get_members", json!({})).await
+    }
+
+    pub async fn get_request(&self, request_id: RequestId) -> Result<MultiSigRequest, ClientError> {
+        self.view("get_request", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn get_num_requests_per_member(
+        &self,
+        member: MultisigMember,
+    ) -> Result<u32, ClientError> {
+        self.view("get_num_requests_per_member", json!({ "member": member }))
+            .await
+    }
+
+    pub async fn list_request_ids(&self) -> Result<Vec<RequestId>, ClientError> {
+        self.view("list_request_ids", json!({})).await
+    }
+
+    pub async fn list_expired_request_ids(&self) -> Result<Vec<RequestId>, ClientError> {
+        self.view("list_expired_request_ids", json!({})).await
+    }
+
+    pub async fn list_queued_requests(&self) -> Result<Vec<(RequestId, U64)>, ClientError> {
+        self.view("list_queued_requests", json!({})).await
+    }
+
+    pub async fn get_timelock_policy(&self) -> Result<Option<TimelockPolicy>, ClientError> {
+        self.view("get_timelock_policy", json!({})).await
+    }
+
+    pub async fn get_request_ttl(&self) -> Result<Option<U64>, ClientError> {
+        self.view("get_request_ttl", json!({})).await
+    }
+
+    pub async fn get_confirmations(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Vec<MultisigMember>, ClientError> {
+        self.view("get_confirmations", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn get_rejections(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Vec<MultisigMember>, ClientError> {
+        self.view("get_rejections", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn get_confirmations_with_timestamps(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Vec<Confirmation>, ClientError> {
+        self.view(
+            "get_confirmations_with_timestamps",
+            json!({ "request_id": request_id }),
+        )
+        .await
+    }
+
+    pub async fn get_num_confirmations(&self) -> Result<u32, ClientError> {
+        self.view("get_num_confirmations", json!({})).await
+    }
+
+    pub async fn get_action_thresholds(&self) -> Result<Vec<(ActionKind, u32)>, ClientError> {
+        self.view("get_action_thresholds", json!({})).await
+    }
+
+    pub async fn get_amount_tiers(
+        &self,
+    ) -> Result<Vec<(Option<AccountId>, Vec<AmountTier>)>, ClientError> {
+        self.view("get_amount_tiers", json!({})).await
+    }
+
+    /// Returns all budgets with the amount spent in their current period.
+    pub async fn get_budgets(&self) -> Result<Vec<BudgetStatus>, ClientError> {
+        self.view("get_budgets", json!({})).await
+    }
+
+    /// Returns the budget of given category and token, `None` for NEAR.
+    pub async fn get_budget(
+        &self,
+        category: String,
+        token_id: Option<AccountId>,
+    ) -> Result<Option<BudgetStatus>, ClientError> {
+        self.view(
+            "get_budget",
+            json!({ "category": category, "token_id": token_id }),
+        )
+        .await
+    }
+
+    pub async fn get_request_threshold(&self, request_id: RequestId) -> Result<u32, ClientError> {
+        self.view("get_request_threshold", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn get_execution_outcome(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Option<ExecutionOutcome>, ClientError> {
+        self.view("get_execution_outcome", json!({ "request_id": request_id }))
+            .await
+    }
+
+    pub async fn get_request_nonce(&self) -> Result<u32, ClientError> {
+        self.view("get_request_nonce", json!({})).await
+    }
+
+    pub async fn get_spendable_balance(&self) -> Result<U128, ClientError> {
+        self.view("get_spendable_balance", json!({})).await
+    }
+
+    pub async fn get_config(&self) -> Result<ContractConfig, ClientError> {
+        self.view("get_config", json!({})).await
+    }
+
+    pub async fn contract_source_metadata(&self) -> Result<ContractSourceMetadata, ClientError> {
+        self.view("contract_source_metadata", json!({})).await
+    }
+
+    /// Returns finished requests, see the `get_history` view for the filters.
+    pub async fn get_history(
+        &self,
+        member: Option<MultisigMember>,
+        action: Option<ActionKind>,
+        from_timestamp: Option<U64>,
+        to_timestamp: Option<U64>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<HistoryEntry>, ClientError> {
+        self.view(
+            "get_history",
+            json!({
+                "member": member,
+                "action": action,
+                "from_timestamp": from_timestamp,
+                "to_timestamp": to_timestamp,
+                "from_index": from_index,
+                "limit": limit,
+            }),
+        )
+        .await
+    }
+
+    pub async fn get_history_length(
+        &self,
+        member: Option<MultisigMember>,
+        from_timestamp: Option<U64>,
+        to_timestamp: Option<U64>,
+    ) -> Result<u64, ClientError> {
+        self.view(
+            "get_history_length",
+            json!({
+                "member": member,
+                "from_timestamp": from_timestamp,
+                "to_timestamp": to_timestamp,
+            }),
+        )
+        .await
+    }
+
+    pub async fn get_incoming_payments(
+        &self,
+        token_id: Option<AccountId>,
+        sender_id: Option<AccountId>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<IncomingPayment>, ClientError> {
+        self.view(
+            "get_incoming_payments",
+            json!({
+                "token_id": token_id,
+                "sender_id": sender_id,
+                "from_index": from_index,
+                "limit": limit,
+            }),
+        )
+        .await
+    }
+
+    pub async fn get_incoming_payments_length(&self) -> Result<u64, ClientError> {
+        self.view("get_incoming_payments_length", json!({})).await
+    }
+
+    pub async fn get_treasury_balance(&self) -> Result<TreasuryBalance, ClientError> {
+        self.view("get_treasury_balance", json!({})).await
+    }
+
+    pub async fn get_treasury_overview(&self) -> Result<TreasuryOverview, ClientError> {
+        self.view("get_treasury_overview", json!({})).await
+    }
+
+    pub async fn get_token_shortfalls(&self) -> Result<Vec<TokenShortfall>, ClientError> {
+        self.view("get_token_shortfalls", json!({})).await
+    }
+
+    pub async fn get_registered_tokens(
+        &self,
+    ) -> Result<Vec<(AccountId, Option<TokenMetadata>)>, ClientError> {
+        self.view("get_registered_tokens", json!({})).await
+    }
+
+    pub async fn get_token_balances(&self) -> Result<Vec<TokenBalance>, ClientError> {
+        self.view("get_token_balances", json!({})).await
+    }
+
+    /// Returns pending escrow transfers, optionally filtered by category and tag of their metadata.
+    pub async fn get_payments(
+        &self,
+        category: Option<String>,
+        tag: Option<String>,
+    ) -> Result<Vec<(CryptoHash, EscrowTransfer)>, ClientError> {
+        self.view("get_payments", json!({ "category": category, "tag": tag }))
+            .await
+    }
+
+    pub async fn get_payment(
+        &self,
+        payment_id: Base58CryptoHash,
+    ) -> Result<EscrowTransfer, ClientError> {
+        self.view("get_payment_by_ud", json!({ "payment_id": payment_id }))
+            .await
+    }
+
+    /********************************
+    Helper methods
+    ********************************/
+
+    async fn view<R: DeserializeOwned>(
+        &self,
+        method_name: &str,
+        args: Value,
+    ) -> Result<R, ClientError> {
+        let result = self
+            .transport
+            .view(
+                &self.contract_id,
+                method_name,
+                args.to_string().into_bytes(),
+            )
+            .await?;
+        parse_result(&result)
+    }
+
+    async fn call<R: DeserializeOwned>(
+        &self,
+        method_name: &str,
+        args: Value,
+    ) -> Result<R, ClientError> {
+        let result = self
+            .transport
+            .call(&self.contract_id, FunctionCall::json(method_name, &args))
+            .await?;
+        parse_result(&result)
+    }
+}
+
+/// Parses the JSON result of a call, an empty result being `null`.
+fn parse_result<R: DeserializeOwned>(result: &[u8]) -> Result<R, ClientError> {
+    let result = if result.is_empty() { b"null" } else { result };
+    serde_json::from_slice(result).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use near_sdk::Gas;
+
+    use super::*;
+
+    /// Transport answering with canned results per method and recording the calls.
+    #[derive(Default)]
+    struct MockTransport {
+        results: HashMap<String, Result<Vec<u8>, ClientError>>,
+        calls: Mutex<Vec<FunctionCall>>,
+    }
+
+    impl MockTransport {
+        fn with_result(
+            mut self,
+            method_name: &str,
+            result: impl near_sdk::serde::Serialize,
+        ) -> Self {
+            self.results.insert(
+                method_name.to_string(),
+                Ok(serde_json::to_vec(&result).unwrap()),
+            );
+            self
+        }
+
+        fn with_error(mut self, method_name: &str, error: ClientError) -> Self {
+            self.results.insert(method_name.to_string(), Err(error));
+            self
+        }
+
+        fn result(&self, method_name: &str) -> Result<Vec<u8>, ClientError> {
+            self.results
+                .get(method_name)
+                .cloned()
+                .unwrap_or_else(|| Ok(vec![]))
+        }
+    }
+
+    impl Transport for MockTransport {
+        async fn view(
+            &self,
+            _contract_id: &AccountId,
+            method_name: &str,
+            args: Vec<u8>,
+        ) -> Result<Vec<u8>, ClientError> {
+            self.calls.lock().unwrap().push(FunctionCall {
+                method_name: method_name.to_string(),
+                args,
+                gas: Gas(0),
+                deposit: 0,
+            });
+            self.result(method_name)
+        }
+
+        async fn call(
+            &self,
+            _contract_id: &AccountId,
+            call: FunctionCall,
+        ) -> Result<Vec<u8>, ClientError> {
+            let result = self.result(&call.method_name);
+            self.calls.lock().unwrap().push(call);
+            result
+        }
+    }
+
+    fn multisig() -> AccountId {
+        "multisig.near".parse().unwrap()
+    }
+
+    fn alice() -> AccountId {
+        "alice.near".parse().unwrap()
+    }
+
+    fn last_call(client: &PaymentsClient<MockTransport>) -> FunctionCall {
+        client
+            .transport
+            .calls
+            .lock()
+            .unwrap()
+            .last()
+            .unwrap()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_add_request_and_confirm() {
+        let transport = MockTransport::default().with_result(
+            "add_request_and_confirm",
+            MultiSigResponse::new(3, FuncResponse::Default(true)),
+        );
+        let client = PaymentsClient::new(transport, multisig());
+        let request = client
+            .request()
+            .near_escrow(
+                alice(),
+                10,
+                PaymentMetadata {
+                    invoice_number: Some("1".to_string()),
+                    ..Default::default()
+                },
+                true,
+            )
+            .build();
+
+        let response = client
+            .add_request_and_confirm(request.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.request_id, 3);
+        assert!(matches!(response.response, FuncResponse::Default(true)));
+
+        let call = last_call(&client);
+        assert_eq!(call.method_name, "add_request_and_confirm");
+        assert_eq!(call.gas, DEFAULT_GAS);
+        assert_eq!(call.deposit, 0);
+        let args: Value = serde_json::from_slice(&call.args).unwrap();
+        assert_eq!(args, json!({ "request": request }));
+    }
+
+    #[tokio::test]
+    async fn test_claim_payment_attaches_deposit() {
+        let client = PaymentsClient::new(MockTransport::default(), multisig());
+        let payment_id = Base58CryptoHash::from([1; 32]);
+        client.claim_payment(payment_id).await.unwrap();
+        let call = last_call(&client);
+        assert_eq!(call.method_name, "claim_payment");
+        assert_eq!(call.deposit, ONE_YOCTO);
+    }
+
+    #[tokio::test]
+    async fn test_view() {
+        let transport = MockTransport::default().with_result("list_request_ids", vec![1, 2]);
+        let client = PaymentsClient::new(transport, multisig());
+        assert_eq!(client.list_request_ids().await.unwrap(), vec![1, 2]);
+        let args: Value = serde_json::from_slice(&last_call(&client).args).unwrap();
+        assert_eq!(args, json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_contract_error() {
+        let transport = MockTransport::default().with_error(
+            "get_request",
+            ClientError::Execution(r#"Smart contract panicked: {"RequestNotFound":3}"#.to_string()),
+        );
+        let client = PaymentsClient::new(transport, multisig());
+        let error = client.get_request(3).await.unwrap_err();
+        assert_eq!(
+            error.contract_error(),
+            Some(ContractError::RequestNotFound(3))
+        );
+        assert_eq!(
+            ClientError::Transport("timeout".into()).contract_error(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_request_builder() {
+        let request = RequestBuilder::new(alice())
+            .transfer(5, PaymentMetadata::default())
+            .set_num_confirmations(2)
+            .build();
+        assert_eq!(
+            request,
+            MultiSigRequest {
+                receiver_id: alice(),
+                actions: vec![
+                    MultiSigRequestAction::Transfer {
+                        amount: U128(5),
+                        metadata: PaymentMetadata::default(),
+                    },
+                    MultiSigRequestAction::SetNumConfirmations {
+                        num_confirmations: 2
+                    },
+                ],
+            }
+        );
+    }
+}
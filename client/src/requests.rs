@@ -0,0 +1,99 @@
+use models::*;
+use near_sdk::{AccountId, Balance};
+
+/// Builds a `MultiSigRequest` action by action.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    receiver_id: AccountId,
+    actions: Vec<MultiSigRequestAction>,
+}
+
+impl RequestBuilder {
+    /// Starts a request on given receiver. Configuration and escrow actions target the multisig itself.
+    pub fn new(receiver_id: AccountId) -> Self {
+        Self {
+            receiver_id,
+            actions: vec![],
+        }
+    }
+
+    pub fn action(mut self, action: MultiSigRequestAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Transfers NEAR to the receiver of the request.
+    pub fn transfer(self, amount: Balance, metadata: PaymentMetadata) -> Self {
+        self.action(MultiSigRequestAction::Transfer {
+            amount: amount.into(),
+            metadata,
+        })
+    }
+
+    /// Creates a NEAR escrow payment that given account can claim.
+    pub fn near_escrow(
+        self,
+        receiver_id: AccountId,
+        amount: Balance,
+        metadata: PaymentMetadata,
+        is_cancellable: bool,
+    ) -> Self {
+        self.action(MultiSigRequestAction::NearEscrowTransfer {
+            receiver_id,
+            amount: amount.into(),
+            metadata,
+            is_cancellable,
+        })
+    }
+
+    /// Creates an escrow payment of a registered token that given account can claim.
+    pub fn ft_escrow(
+        self,
+        token_id: AccountId,
+        receiver_id: AccountId,
+        amount: Balance,
+        metadata: PaymentMetadata,
+        is_cancellable: bool,
+    ) -> Self {
+        self.action(MultiSigRequestAction::FTEscrowTransfer {
+            receiver_id,
+            amount: amount.into(),
+            token_id,
+            metadata,
+            is_cancellable,
+        })
+    }
+
+    pub fn add_member(self, member: MultisigMember) -> Self {
+        self.action(MultiSigRequestAction::AddMember { member })
+    }
+
+    pub fn delete_member(self, member: MultisigMember) -> Self {
+        self.action(MultiSigRequestAction::DeleteMember { member })
+    }
+
+    pub fn set_num_confirmations(self, num_confirmations: u32) -> Self {
+        self.action(MultiSigRequestAction::SetNumConfirmations { num_confirmations })
+    }
+
+    /// Sets the budget of given category and token (`None` for NEAR), `None` removes it.
+    pub fn set_budget(
+        self,
+        category: impl Into<String>,
+        token_id: Option<AccountId>,
+        budget: Option<Budget>,
+    ) -> Self {
+        self.action(MultiSigRequestAction::SetBudget {
+            category: category.into(),
+            token_id,
+            budget,
+        })
+    }
+
+    pub fn build(self) -> MultiSigRequest {
+        MultiSigRequest {
+            receiver_id: self.receiver_id,
+            actions: self.actions,
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! `Transport` over the NEAR JSON-RPC API, enabled by the `rpc` feature.
+//!
+//! Views are sent as `call_function` queries. Calls are signed with an in-memory key,
+//! using the current nonce of the access key, and sent with `broadcast_tx_commit`.
+
+use std::str::FromStr;
+
+use near_crypto::{InMemorySigner, Signer};
+use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::{from_base64, to_base64};
+use near_primitives::transaction::{Action, FunctionCallAction, SignedTransaction, Transaction};
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::serde_json::{json, Value};
+use near_sdk::AccountId;
+
+use crate::{ClientError, FunctionCall, Transport};
+
+pub const MAINNET_RPC_URL: &str = "https://rpc.mainnet.near.org";
+pub const TESTNET_RPC_URL: &str = "https://rpc.testnet.near.org";
+
+/// JSON-RPC client, with the key signing change calls.
+pub struct JsonRpcTransport {
+    http: reqwest::Client,
+    url: String,
+    signer: Option<InMemorySigner>,
+}
+
+impl JsonRpcTransport {
+    /// Creates a transport for view calls, see `with_signer` to send change calls.
+    pub fn new(url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.to_string(),
+            signer: None,
+        }
+    }
+
+    /// Signs change calls with given key, it must be a full access key or a multisig key of its account.
+    pub fn with_signer(mut self, signer: InMemorySigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Sends a JSON-RPC request and returns its result.
+    /// Contract failures of view calls are returned as `ClientError::Execution`.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let response: Value = self
+            .http
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": "payments",
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .map_err(|e| ClientError::Transport(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(match error["cause"]["name"].as_str() {
+                Some("CONTRACT_EXECUTION_ERROR") => {
+                    ClientError::Execution(error_message(&error["cause"]["info"]["vm_error"]))
+                }
+                _ => ClientError::Transport(error_message(error)),
+            });
+        }
+        let result = response["result"].clone();
+        // older nodes report contract failures of views in the result
+        if let Some(error) = result.get("error") {
+            return Err(ClientError::Execution(error_message(error)));
+        }
+        Ok(result)
+    }
+
+    /// Returns the next nonce of the access key of the signer and a recent block hash.
+    async fn access_key(&self, signer: &InMemorySigner) -> Result<(u64, CryptoHash), ClientError> {
+        let result = self
+            .request(
+                "query",
+                json!({
+                    "request_type": "view_access_key",
+                    "finality": "final",
+                    "account_id": signer.account_id,
+                    "public_key": signer.public_key,
+                }),
+            )
+            .await?;
+        let nonce = result["nonce"]
+            .as_u64()
+            .ok_or_else(|| ClientError::InvalidResponse("missing nonce".to_string()))?;
+        let block_hash = result["block_hash"]
+            .as_str()
+            .and_then(|hash| CryptoHash::from_str(hash).ok())
+            .ok_or_else(|| ClientError::InvalidResponse("missing block hash".to_string()))?;
+        Ok((nonce + 1, block_hash))
+    }
+}
+
+impl Transport for JsonRpcTransport {
+    async fn view(
+        &self,
+        contract_id: &AccountId,
+        method_name: &str,
+        args: Vec<u8>,
+    ) -> Result<Vec<u8>, ClientError> {
+        let result = self
+            .request(
+                "query",
+                json!({
+                    "request_type": "call_function",
+                    "finality": "final",
+                    "account_id": contract_id,
+                    "method_name": method_name,
+                    "args_base64": to_base64(args),
+                }),
+            )
+            .await?;
+        result["result"]
+            .as_array()
+            .ok_or_else(|| ClientError::InvalidResponse("missing result".to_string()))?
+            .iter()
+            .map(|byte| {
+                byte.as_u64()
+                    .and_then(|byte| u8::try_from(byte).ok())
+                    .ok_or_else(|| ClientError::InvalidResponse("invalid result".to_string()))
+            })
+            .collect()
+    }
+
+    async fn call(
+        &self,
+        contract_id: &AccountId,
+        call: FunctionCall,
+    ) -> Result<Vec<u8>, ClientError> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or_else(|| ClientError::Transport("no signer for change calls".to_string()))?;
+        let (nonce, block_hash) = self.access_key(signer).await?;
+        let transaction = Transaction {
+            signer_id: signer.account_id.clone(),
+            public_key: signer.public_key.clone(),
+            nonce,
+            receiver_id: contract_id
+                .as_str()
+                .parse()
+                .map_err(|e| ClientError::Transport(format!("{e}")))?,
+            block_hash,
+            actions: vec![Action::FunctionCall(FunctionCallAction {
+                method_name: call.method_name,
+                args: call.args,
+                gas: call.gas.0,
+                deposit: call.deposit,
+            })],
+        };
+        let (hash, _) = transaction.get_hash_and_size();
+        let transaction = SignedTransaction::new(signer.sign(hash.as_ref()), transaction);
+        let transaction = transaction
+            .try_to_vec()
+            .map_err(|e| ClientError::Transport(e.to_string()))?;
+
+        let outcome = self
+            .request("broadcast_tx_commit", json!([to_base64(transaction)]))
+            .await?;
+        outcome_result(&outcome["status"])
+    }
+}
+
+/// Returns the value of a final transaction status, or its failure.
+fn outcome_result(status: &Value) -> Result<Vec<u8>, ClientError> {
+    if let Some(value) = status.get("SuccessValue") {
+        return from_base64(value.as_str().unwrap_or_default())
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()));
+    }
+    match status.get("Failure") {
+        // contract panics hold the `ContractError`, see `ClientError::contract_error`
+        Some(failure) => Err(ClientError::Execution(error_message(
+            failure
+                .pointer("/ActionError/kind/FunctionCallError/ExecutionError")
+                .unwrap_or(failure),
+        ))),
+        None => Err(ClientError::InvalidResponse(format!(
+            "unexpected status: {status}"
+        ))),
+    }
+}
+
+/// Returns a string error as is and other errors as JSON.
+fn error_message(error: &Value) -> String {
+    error
+        .as_str()
+        .map_or_else(|| error.to_string(), str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use models::ContractError;
+
+    use super::*;
+
+    /// Serves one HTTP request with given JSON-RPC response and returns the URL to reach it.
+    async fn serve(response: Value) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = socket.read(&mut request).await.unwrap();
+            let body = response.to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    fn contract_id() -> AccountId {
+        "multisig.near".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_view() {
+        let url = serve(json!({
+            "jsonrpc": "2.0",
+            "id": "payments",
+            "result": { "result": b"[1,2]".to_vec(), "logs": [], "block_height": 1 },
+        }))
+        .await;
+        let transport = JsonRpcTransport::new(&url);
+        let result = transport
+            .view(&contract_id(), "list_request_ids", b"{}".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(result, b"[1,2]".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_view_error() {
+        let url = serve(json!({
+            "jsonrpc": "2.0",
+            "id": "payments",
+            "error": {
+                "name": "HANDLER_ERROR",
+                "cause": {
+                    "name": "CONTRACT_EXECUTION_ERROR",
+                    "info": { "vm_error": "Smart contract panicked: {\"RequestNotFound\":3}" },
+                },
+            },
+        }))
+        .await;
+        let error = JsonRpcTransport::new(&url)
+            .view(&contract_id(), "get_request", b"{}".to_vec())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.contract_error(),
+            Some(ContractError::RequestNotFound(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_without_signer() {
+        let transport = JsonRpcTransport::new("http://127.0.0.1:0");
+        let error = transport
+            .call(&contract_id(), FunctionCall::json("confirm", &json!({})))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ClientError::Transport(_)));
+    }
+
+    #[test]
+    fn test_outcome_result() {
+        assert_eq!(
+            outcome_result(&json!({ "SuccessValue": to_base64(b"true") })),
+            Ok(b"true".to_vec())
+        );
+        let failure = json!({
+            "Failure": {
+                "ActionError": {
+                    "index": 0,
+                    "kind": {
+                        "FunctionCallError": {
+                            "ExecutionError": "Smart contract panicked: \"NotAMember\""
+                        }
+                    }
+                }
+            }
+        });
+        assert_eq!(
+            outcome_result(&failure).unwrap_err().contract_error(),
+            Some(ContractError::NotAMember)
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use std::future::Future;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{AccountId, Balance, Gas};
+
+use crate::ClientError;
+
+/// Gas attached to change calls, enough for requests executing cross-contract calls.
+pub const DEFAULT_GAS: Gas = Gas(300_000_000_000_000);
+
+/// Function call on the payments contract, ready to be signed.
+/// The Borsh encoding matches the `FunctionCall` action of a transaction.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct FunctionCall {
+    pub method_name: String,
+    /// JSON encoded arguments.
+    pub args: Vec<u8>,
+    pub gas: Gas,
+    pub deposit: Balance,
+}
+
+impl FunctionCall {
+    /// Creates a call with JSON encoded arguments, `DEFAULT_GAS` and no deposit.
+    pub fn json(method_name: &str, args: &impl Serialize) -> Self {
+        Self {
+            method_name: method_name.to_string(),
+            args: serde_json::to_vec(args).expect("arguments are serializable"),
+            gas: DEFAULT_GAS,
+            deposit: 0,
+        }
+    }
+
+    pub fn with_deposit(mut self, deposit: Balance) -> Self {
+        self.deposit = deposit;
+        self
+    }
+
+    pub fn with_gas(mut self, gas: Gas) -> Self {
+        self.gas = gas;
+        self
+    }
+}
+
+/// RPC layer of `PaymentsClient`, usually a JSON-RPC client with a signer such as `JsonRpcTransport`.
+/// Tests replace it with a local mock.
+pub trait Transport {
+    /// Calls a view method with JSON encoded arguments and returns the raw result.
+    fn view(
+        &self,
+        contract_id: &AccountId,
+        method_name: &str,
+        args: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>, ClientError>> + Send;
+
+    /// Signs and sends given call, waits for its final outcome and returns the raw result.
+    /// Failed executions are returned as `ClientError::Execution`.
+    fn call(
+        &self,
+        contract_id: &AccountId,
+        call: FunctionCall,
+    ) -> impl Future<Output = Result<Vec<u8>, ClientError>> + Send;
+}
@@ -0,0 +1,223 @@
+use near_sdk::near_bindgen;
+
+use crate::*;
+
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+
+/// Category and token of a budget, `None` for NEAR.
+pub type BudgetKey = (String, Option<AccountId>);
+
+/// Amount spent from a budget in the period starting at `period_start`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BudgetSpending {
+    pub period_start: u64,
+    pub spent: u128,
+}
+
+impl Contract {
+    /// Sets or removes given budget. Changing the amount or the period keeps what was spent in the current period.
+    pub(crate) fn set_budget(
+        &mut self,
+        category: String,
+        token_id: Option<AccountId>,
+        budget: Option<Budget>,
+    ) -> Result<(), ContractError> {
+        check_category(Some(&category))?;
+        let key = (category, token_id);
+        match budget {
+            Some(budget) => {
+                // what was spent in the current period counts towards the current period of the new budget
+                let now = env::block_timestamp();
+                if let Some(old) = self.budgets.insert(&key, &budget) {
+                    let spent = self.spent(&key, period_bounds(old.period, now).0);
+                    let (period_start, _) = period_bounds(budget.period, now);
+                    if spent > 0 {
+                        self.budget_spending.insert(
+                            &key,
+                            &BudgetSpending {
+                                period_start,
+                                spent,
+                            },
+                        );
+                    }
+                }
+            }
+            None => {
+                self.budgets.remove(&key);
+                self.budget_spending.remove(&key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Charges the payments of given request to the budgets of their category.
+    /// Fails if a budget would be exceeded, payments of categories without budget are not limited.
+    pub(crate) fn spend_budgets(&mut self, request: &MultiSigRequest) -> Result<(), ContractError> {
+        let now = env::block_timestamp();
+        let mut spending = vec![];
+        for (key, amount) in budget_outflow(&request.payments()) {
+            let budget = match self.budgets.get(&key) {
+                Some(budget) => budget,
+                None => continue,
+            };
+            let (period_start, _) = period_bounds(budget.period, now);
+            let spent = self.spent(&key, period_start);
+            let remaining = budget.amount.0.saturating_sub(spent);
+            if amount > remaining {
+                return Err(ContractError::BudgetExceeded {
+                    category: key.0,
+                    token_id: key.1,
+                    remaining,
+                    requested: amount,
+                });
+            }
+            spending.push((
+                key,
+                BudgetSpending {
+                    period_start,
+                    spent: spent + amount,
+                },
+            ));
+        }
+        for (key, spending) in spending {
+            self.budget_spending.insert(&key, &spending);
+        }
+        Ok(())
+    }
+
+    /// Gives back given payments to the budgets they were charged to at `charged_at`.
+    /// Nothing is given back once the period of the charge is over.
+    pub(crate) fn refund_budgets(&mut self, payments: &[PaymentSummary], charged_at: u64) {
+        for (key, amount) in budget_outflow(payments) {
+            let budget = match self.budgets.get(&key) {
+                Some(budget) => budget,
+                None => continue,
+            };
+            let (period_start, _) = period_bounds(budget.period, charged_at);
+            let spent = self.spent(&key, period_start);
+            if spent > 0 {
+                self.budget_spending.insert(
+                    &key,
+                    &BudgetSpending {
+                        period_start,
+                        spent: spent.saturating_sub(amount),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Returns the amount spent from given budget in the period starting at `period_start`.
+    fn spent(&self, key: &BudgetKey, period_start: u64) -> u128 {
+        self.budget_spending
+            .get(key)
+            .filter(|spending| spending.period_start == period_start)
+            .map_or(0, |spending| spending.spent)
+    }
+
+    fn budget_status(&self, key: BudgetKey, budget: Budget) -> BudgetStatus {
+        let (period_start, period_end) = period_bounds(budget.period, env::block_timestamp());
+        let spent = self.spent(&key, period_start);
+        BudgetStatus {
+            remaining: budget.amount.0.saturating_sub(spent).into(),
+            category: key.0,
+            token_id: key.1,
+            budget,
+            period_start: period_start.into(),
+            period_end: period_end.into(),
+            spent: spent.into(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns all budgets with the amount spent in their current period.
+    pub fn get_budgets(&self) -> Vec<BudgetStatus> {
+        self.budgets
+            .iter()
+            .map(|(key, budget)| self.budget_status(key, budget))
+            .collect()
+    }
+
+    /// Returns the budget of given category and token (`None` for NEAR) with the amount spent in its current period.
+    pub fn get_budget(
+        &self,
+        category: String,
+        token_id: Option<AccountId>,
+    ) -> Option<BudgetStatus> {
+        let key = (category, token_id);
+        self.budgets
+            .get(&key)
+            .map(|budget| self.budget_status(key, budget))
+    }
+}
+
+/// Fails if a payment has no category, or if the category is too long.
+pub(crate) fn check_category(category: Option<&String>) -> Result<(), ContractError> {
+    match category {
+        Some(category) if category.len() > MAX_METADATA_FIELD_LEN as usize => {
+            Err(ContractError::MetadataTooLong {
+                field: "category".to_string(),
+                max_len: MAX_METADATA_FIELD_LEN,
+            })
+        }
+        Some(category) if !category.is_empty() => Ok(()),
+        _ => Err(ContractError::MissingCategory),
+    }
+}
+
+/// Returns total amount of given payments per category and token (`None` for NEAR).
+/// Payments of requests added before categories were required are not charged.
+fn budget_outflow(payments: &[PaymentSummary]) -> HashMap<BudgetKey, u128> {
+    request_outflow(payments, |payment| {
+        let category = payment.metadata.category.clone()?;
+        Some((category, payment.token_id.clone()))
+    })
+}
+
+/// Returns the bounds in nanoseconds of the period containing given timestamp, the end excluded.
+pub(crate) fn period_bounds(period: BudgetPeriod, timestamp: u64) -> (u64, u64) {
+    let (year, month) = civil_from_days((timestamp / NANOS_PER_DAY) as i64);
+    let (first_month, num_months) = match period {
+        BudgetPeriod::Month => (month, 1),
+        BudgetPeriod::Quarter => ((month - 1) / 3 * 3 + 1, 3),
+        BudgetPeriod::Year => (1, 12),
+    };
+    // months past December roll over to the next year
+    let next = first_month + num_months - 1;
+    (
+        month_start(year, first_month),
+        month_start(year + (next / 12) as i64, next % 12 + 1),
+    )
+}
+
+/// Returns the timestamp in nanoseconds of the first day of given month.
+fn month_start(year: i64, month: u32) -> u64 {
+    days_from_civil(year, month) as u64 * NANOS_PER_DAY
+}
+
+/// Returns year and month (1 to 12) of given day since the Unix epoch, see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month as u32)
+}
+
+/// Returns the day since the Unix epoch of the first day of given month, see
+/// <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -1,2 +1 @@
-pub mod errors;
 pub mod primitives;
@@ -12,17 +12,18 @@ impl Contract {
         &mut self,
         receiver_id: AccountId,
         amount: u128,
-        label: String,
+        metadata: PaymentMetadata,
         is_locked: bool,
-    ) -> Result<CryptoHash, String> {
-        // check near balance is sufficient
-        assert!(
-            env::account_balance() >= self.near_committed_balance + amount,
-            "Not enough NEAR balance"
-        );
+    ) -> Result<CryptoHash, ContractError> {
+        // the balance is checked for the whole request before executing it
 
         // update committed balance
         self.near_committed_balance += amount;
+        PaymentsEvent::CommittedBalanceChanged {
+            token_id: None,
+            committed: self.near_committed_balance.into(),
+        }
+        .emit();
 
         // create escrow payment
         let mut buf = env::random_seed();
@@ -33,34 +34,36 @@ impl Contract {
             id,
             receiver_id,
             amount,
-            label,
+            metadata,
             is_locked,
             token_account: None,
         };
 
-        self.escrow_transfers.insert(&id, &p);
+        emit_escrow_created(&p);
+        self.escrow_transfers.insert(&id, &p.into());
 
         // return escrow payment id
-        Ok(p.id)
+        Ok(id)
     }
 }
 
 #[near_bindgen]
 impl Contract {
     #[private]
+    #[handle_result]
     pub fn callback_create_ft_escrow(
         &mut self,
         receiver_id: AccountId,
         amount: u128,
-        label: String,
+        metadata: PaymentMetadata,
         is_locked: bool,
         token_account: AccountId,
         #[callback_result] balance: Result<U128, near_sdk::PromiseError>,
-    ) -> MultiSigResponse {
-        let balance: u128 = match balance {
-            Ok(b) => b.into(),
-            Err(e) => env::panic_str(&format!("Error from ft_balance_of: {:?}", e)),
-        };
+    ) -> Result<MultiSigResponse, ContractError> {
+        let balance: u128 = balance
+            .map_err(|_| ContractError::TokenBalanceUnavailable(token_account.clone()))?
+            .into();
+        self.cache_token_balance(&token_account, balance);
 
         // assert token account is registered
         if self.ft_committed_balance.get(&token_account).is_none() {
@@ -70,18 +73,24 @@ impl Contract {
         let mut committed_balance = self.ft_committed_balance.get(&token_account).unwrap();
 
         // check ft balance is sufficient
-        assert!(
-            balance >= committed_balance + amount,
-            "Not enough {} balance, current balance: {}, committed balance: {}",
-            token_account,
-            balance,
-            committed_balance
-        );
+        let available = balance.saturating_sub(committed_balance);
+        if amount > available {
+            return Err(ContractError::InsufficientBalance {
+                token_id: Some(token_account),
+                available,
+                requested: amount,
+            });
+        }
 
         // update committed balance
         committed_balance += amount;
         self.ft_committed_balance
             .insert(&token_account, &committed_balance);
+        PaymentsEvent::CommittedBalanceChanged {
+            token_id: Some(token_account.clone()),
+            committed: committed_balance.into(),
+        }
+        .emit();
 
         // create escrow payment
         let mut buf = env::random_seed();
@@ -92,14 +101,29 @@ impl Contract {
             id,
             receiver_id,
             amount,
-            label,
+            metadata,
             is_locked,
             token_account: Some(token_account),
         };
 
-        self.escrow_transfers.insert(&id, &p);
+        emit_escrow_created(&p);
+        self.escrow_transfers.insert(&id, &p.into());
 
         // return escrow payment id
-        MultiSigResponse::new(0, FuncResponse::EscrowPayment(id.into()))
+        Ok(MultiSigResponse::new(
+            0,
+            FuncResponse::EscrowPayment(id.into()),
+        ))
+    }
+}
+
+fn emit_escrow_created(p: &EscrowTransfer) {
+    PaymentsEvent::EscrowCreated {
+        payment_id: p.id.into(),
+        receiver_id: p.receiver_id.clone(),
+        amount: p.amount.into(),
+        token_id: p.token_account.clone(),
+        metadata: p.metadata.clone(),
     }
+    .emit();
 }
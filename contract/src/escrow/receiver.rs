@@ -1,9 +1,6 @@
 use near_sdk::{json_types::Base58CryptoHash, near_bindgen, ONE_YOCTO};
 
-use crate::{
-    common::{errors::ContractError, primitives::check_deposit},
-    *,
-};
+use crate::{common::primitives::check_deposit, *};
 
 use near_units::parse_near;
 
@@ -17,9 +14,10 @@ impl Contract {
     ) -> Result<Promise, ContractError> {
         check_deposit(ONE_YOCTO)?;
 
-        let p = self
+        let p: EscrowTransfer = self
             .escrow_transfers
             .get(&payment_id.into())
+            .map(Into::into)
             .ok_or_else(|| ContractError::EscrowTransferNotFound("in claim payment".into()))?;
 
         // assert called by receiver
@@ -27,6 +25,14 @@ impl Contract {
             return Err(ContractError::NotAuthorized);
         }
 
+        PaymentsEvent::EscrowClaimed {
+            payment_id,
+            receiver_id: p.receiver_id.clone(),
+            amount: p.amount.into(),
+            token_id: p.token_account.clone(),
+        }
+        .emit();
+
         if p.token_account.is_some() {
             // update committed balance
             let curr = self
@@ -37,6 +43,11 @@ impl Contract {
 
             self.ft_committed_balance
                 .insert(&p.token_account.clone().unwrap(), &new);
+            PaymentsEvent::CommittedBalanceChanged {
+                token_id: p.token_account.clone(),
+                committed: new.into(),
+            }
+            .emit();
 
             // remove payment from escrow
             self.escrow_transfers.remove(&payment_id.into());
@@ -52,6 +63,11 @@ impl Contract {
                 ))
         } else {
             self.near_committed_balance -= p.amount;
+            PaymentsEvent::CommittedBalanceChanged {
+                token_id: None,
+                committed: self.near_committed_balance.into(),
+            }
+            .emit();
 
             // remove payment from escrow
             self.escrow_transfers.remove(&payment_id.into());
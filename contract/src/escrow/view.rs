@@ -1,17 +1,25 @@
 use near_sdk::json_types::Base58CryptoHash;
 
-use crate::{
-    common::errors::{ContractError},
-    *,
-};
+use crate::*;
 
 #[near_bindgen]
 impl Contract {
+    /// Returns pending escrow transfers, optionally filtered by category and tag of their metadata.
     #[handle_result]
-    pub fn get_payments(&self) -> Result<Vec<(CryptoHash, EscrowTransfer)>, String> {
+    pub fn get_payments(
+        &self,
+        category: Option<String>,
+        tag: Option<String>,
+    ) -> Result<Vec<(CryptoHash, EscrowTransfer)>, String> {
         let mut payments = vec![];
         for (k, v) in self.escrow_transfers.iter() {
-            payments.push((k, v));
+            let payment: EscrowTransfer = v.into();
+            if payment
+                .metadata
+                .matches(category.as_deref(), tag.as_deref())
+            {
+                payments.push((k, payment));
+            }
         }
         Ok(payments)
     }
@@ -23,6 +31,7 @@ impl Contract {
     ) -> Result<EscrowTransfer, ContractError> {
         self.escrow_transfers
             .get(&payment_id.into())
+            .map(Into::into)
             .ok_or_else(|| ContractError::EscrowTransferNotFound("payment not found".into()))
     }
 }
@@ -0,0 +1,159 @@
+use near_sdk::json_types::U64;
+use near_sdk::near_bindgen;
+
+use crate::*;
+
+/// Positions of history entries in a time range, in the history or in the entries of a member.
+struct HistoryRange {
+    /// Indices in the history of the entries of a member, `None` for the whole history.
+    indices: Option<Vector<u64>>,
+    start: u64,
+    end: u64,
+}
+
+impl HistoryRange {
+    /// Returns the index in the history of the entry at given position.
+    fn index(&self, position: u64) -> u64 {
+        match &self.indices {
+            Some(indices) => indices.get(position).unwrap_or(u64::MAX),
+            None => position,
+        }
+    }
+}
+
+impl Contract {
+    /// Appends a finished request to the history and returns its index.
+    pub(crate) fn archive_request(&mut self, entry: &HistoryEntry) -> u64 {
+        self.history.push(entry);
+        let index = self.history.len() - 1;
+        index_member_history(&mut self.member_history, index, entry);
+        index
+    }
+
+    /// Sets the final status of an archived request once its execution result is known, and returns the entry.
+    pub(crate) fn update_archived_request(
+        &mut self,
+        index: u64,
+        success: bool,
+        response: &FuncResponse,
+    ) -> Result<HistoryEntry, ContractError> {
+        let mut entry = self
+            .history
+            .get(index)
+            .ok_or(ContractError::HistoryEntryNotFound(index))?;
+        entry.status = RequestStatus::Executed { success };
+        if let FuncResponse::EscrowPayment(payment_id) = response {
+            entry.payment_ids.push(*payment_id);
+        }
+        self.history.replace(index, &entry);
+        Ok(entry)
+    }
+
+    /// Returns the positions of the entries of given member, or of all entries, finished in given time range.
+    /// Entries are archived in the order they finish, so the bounds are found by binary search.
+    fn history_range(
+        &self,
+        member: Option<MultisigMember>,
+        from_timestamp: Option<U64>,
+        to_timestamp: Option<U64>,
+    ) -> HistoryRange {
+        let indices = member.map(|member| {
+            self.member_history
+                .get(&member)
+                .unwrap_or_else(|| Vector::new(StorageKeys::MemberHistoryIndices { member }))
+        });
+        let len = indices.as_ref().map_or(self.history.len(), Vector::len);
+        let mut range = HistoryRange {
+            indices,
+            start: 0,
+            end: len,
+        };
+        let finished_at = |position| {
+            self.history
+                .get(range.index(position))
+                .map_or(u64::MAX, |entry| entry.finished_at.0)
+        };
+        let start = from_timestamp.map_or(0, |from| {
+            partition_point(len, |position| finished_at(position) < from.0)
+        });
+        let end = to_timestamp.map_or(len, |to| {
+            partition_point(len, |position| finished_at(position) <= to.0)
+        });
+        range.start = start;
+        range.end = end.max(start);
+        range
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns finished requests, oldest first.
+    /// Optionally restricted to a member who proposed or confirmed them and to the time they finished
+    /// (inclusive bounds, in nanoseconds), and to requests with an action of given type.
+    /// `from_index` and `limit` select positions among the matching entries; without an action type
+    /// their number is `get_history_length`.
+    pub fn get_history(
+        &self,
+        member: Option<MultisigMember>,
+        action: Option<ActionKind>,
+        from_timestamp: Option<U64>,
+        to_timestamp: Option<U64>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<HistoryEntry> {
+        let range = self.history_range(member, from_timestamp, to_timestamp);
+        (range.start..range.end)
+            .filter_map(|position| self.history.get(range.index(position)))
+            .filter(|e| {
+                action
+                    .iter()
+                    .all(|k| e.request.actions.iter().any(|a| &a.kind() == k))
+            })
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(u64::MAX) as usize)
+            .collect()
+    }
+
+    /// Returns number of finished requests in the history,
+    /// optionally restricted to a member and to a time range as in `get_history`.
+    pub fn get_history_length(
+        &self,
+        member: Option<MultisigMember>,
+        from_timestamp: Option<U64>,
+        to_timestamp: Option<U64>,
+    ) -> u64 {
+        let range = self.history_range(member, from_timestamp, to_timestamp);
+        range.end - range.start
+    }
+}
+
+/// Records the history entry at given index for the members who proposed or confirmed it.
+pub(crate) fn index_member_history(
+    member_history: &mut LookupMap<MultisigMember, Vector<u64>>,
+    index: u64,
+    entry: &HistoryEntry,
+) {
+    for member in entry.members() {
+        let mut indices = member_history.get(&member).unwrap_or_else(|| {
+            Vector::new(StorageKeys::MemberHistoryIndices {
+                member: member.clone(),
+            })
+        });
+        indices.push(&index);
+        member_history.insert(&member, &indices);
+    }
+}
+
+/// Returns the first position in `0..len` for which `pred` is false, `pred` being true then false.
+fn partition_point(len: u64, pred: impl Fn(u64) -> bool) -> u64 {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
@@ -0,0 +1,105 @@
+use near_sdk::{json_types::U128, near_bindgen};
+
+use crate::*;
+
+/// Maximum length of the message recorded with an incoming payment.
+const MAX_MEMO_LEN: u32 = 256;
+
+#[near_bindgen]
+impl Contract {
+    /// NEP-141 receiver: accepts tokens from registered token contracts and records the deposit.
+    /// The `msg` of `ft_transfer_call` is recorded as the payment memo.
+    #[handle_result]
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> Result<PromiseOrValue<U128>, ContractError> {
+        let token_id = env::predecessor_account_id();
+        self.check_registered_token(&token_id)?;
+        if msg.len() > MAX_MEMO_LEN as usize {
+            return Err(ContractError::MemoTooLong {
+                max_len: MAX_MEMO_LEN,
+            });
+        }
+
+        let total = self
+            .incoming_totals
+            .get(&token_id)
+            .unwrap_or(0)
+            .checked_add(amount.0)
+            .ok_or_else(|| ContractError::IncomingTotalOverflow(token_id.clone()))?;
+        self.incoming_totals.insert(&token_id, &total);
+
+        let memo = if msg.is_empty() { None } else { Some(msg) };
+        self.incoming_payments.push(&IncomingPayment {
+            token_id: token_id.clone(),
+            sender_id: sender_id.clone(),
+            amount,
+            memo: memo.clone(),
+            timestamp: env::block_timestamp().into(),
+        });
+        let key = (token_id.clone(), sender_id.clone());
+        let mut indices = self.incoming_payment_index.get(&key).unwrap_or_else(|| {
+            Vector::new(StorageKeys::IncomingPaymentIndices {
+                token_id: token_id.clone(),
+                sender_id: sender_id.clone(),
+            })
+        });
+        indices.push(&(self.incoming_payments.len() - 1));
+        self.incoming_payment_index.insert(&key, &indices);
+        PaymentsEvent::PaymentReceived {
+            token_id,
+            sender_id,
+            amount,
+            memo,
+        }
+        .emit();
+
+        // all tokens are kept
+        Ok(PromiseOrValue::Value(U128(0)))
+    }
+
+    /// Returns incoming FT payments, optionally filtered by token and sender, oldest first.
+    /// With both a token and a sender, `from_index` and `limit` select positions among the payments
+    /// of the sender. Otherwise they select positions among all payments before filtering,
+    /// so a page can hold fewer than `limit` payments, see `get_incoming_payments_length`.
+    pub fn get_incoming_payments(
+        &self,
+        token_id: Option<AccountId>,
+        sender_id: Option<AccountId>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<IncomingPayment> {
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(u64::MAX);
+        if let (Some(token_id), Some(sender_id)) = (&token_id, &sender_id) {
+            let indices = match self
+                .incoming_payment_index
+                .get(&(token_id.clone(), sender_id.clone()))
+            {
+                Some(indices) => indices,
+                None => return vec![],
+            };
+            let to_index = from_index.saturating_add(limit).min(indices.len());
+            return (from_index..to_index)
+                .filter_map(|position| indices.get(position))
+                .filter_map(|index| self.incoming_payments.get(index))
+                .collect();
+        }
+        let to_index = from_index
+            .saturating_add(limit)
+            .min(self.incoming_payments.len());
+        (from_index..to_index)
+            .filter_map(|index| self.incoming_payments.get(index))
+            .filter(|p| token_id.iter().all(|t| t == &p.token_id))
+            .filter(|p| sender_id.iter().all(|s| s == &p.sender_id))
+            .collect()
+    }
+
+    /// Returns number of incoming payments, the positions paginated by `get_incoming_payments`.
+    pub fn get_incoming_payments_length(&self) -> u64 {
+        self.incoming_payments.len()
+    }
+}
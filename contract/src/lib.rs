@@ -1,18 +1,26 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use models::*;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
 
-use near_sdk::json_types::U128;
+use budgets::{BudgetKey, BudgetSpending};
+use migration::{MultiSigRequestWithSignerV1, STATE_VERSION};
+use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, ext_contract, near_bindgen, serde_json, AccountId, BorshStorageKey, CryptoHash,
-    PanicOnDefault, Promise, PromiseOrValue,
+    env, ext_contract, near_bindgen, serde_json, AccountId, Balance, BorshStorageKey, CryptoHash,
+    Gas, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, ONE_NEAR,
 };
 
+pub mod budgets;
 pub mod common;
 pub mod escrow;
+pub mod history;
+pub mod incoming;
+pub mod migration;
+pub mod staking;
+pub mod tokens;
 
 /// Unlimited allowance for multisig keys.
 const DEFAULT_ALLOWANCE: u128 = 0;
@@ -23,8 +31,22 @@ const REQUEST_COOLDOWN: u64 = 900_000_000_000;
 /// Default limit of active requests.
 const ACTIVE_REQUESTS_LIMIT: u32 = 12;
 
+/// Default NEAR kept on top of storage staking, covers storage growth between payments.
+const DEFAULT_STORAGE_RESERVE: Balance = ONE_NEAR / 2;
+
+/// Maximum size in bytes of the text fields of payment metadata.
+const MAX_METADATA_FIELD_LEN: u32 = 128;
+
+/// Maximum number of tags of a payment, and size in bytes of each tag.
+const MAX_TAGS: u32 = 8;
+const MAX_TAG_LEN: u32 = 32;
+
+/// Gas attached to `migrate` after upgrading the multisig itself.
+const MIGRATE_GAS: Gas = Gas(50_000_000_000_000);
+
 /// Default set of methods that access key should have.
-const MULTISIG_METHOD_NAMES: &str = "add_request,delete_request,confirm,add_and_confirm_request";
+const MULTISIG_METHOD_NAMES: &str =
+    "add_request,delete_request,confirm,add_and_confirm_request,execute,veto,revoke_confirmation,reject";
 
 #[ext_contract(ext_nep141_token)]
 pub trait ExtNep141Token {
@@ -40,6 +62,7 @@ pub trait ExtNep141Token {
         account_id: AccountId,
         registration_only: Option<bool>,
     ) -> Promise;
+    fn ft_metadata(&self) -> Promise;
 }
 
 /// An internal request wrapped with the signer_pk and added timestamp to determine num_requests_pk and prevent against malicious key holder gas attacks
@@ -50,6 +73,30 @@ pub struct MultiSigRequestWithSigner {
     request: MultiSigRequest,
     member: MultisigMember,
     added_timestamp: u64,
+    /// Set once the request is confirmed but timelocked.
+    executable_at: Option<u64>,
+}
+
+/// Stored layouts of `MultiSigRequestWithSigner`, requests of the first release are converted when read.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedMultiSigRequestWithSigner {
+    V1(MultiSigRequestWithSignerV1),
+    V2(MultiSigRequestWithSigner),
+}
+
+impl From<VersionedMultiSigRequestWithSigner> for MultiSigRequestWithSigner {
+    fn from(request: VersionedMultiSigRequestWithSigner) -> Self {
+        match request {
+            VersionedMultiSigRequestWithSigner::V1(request) => request.into(),
+            VersionedMultiSigRequestWithSigner::V2(request) => request,
+        }
+    }
+}
+
+impl From<MultiSigRequestWithSigner> for VersionedMultiSigRequestWithSigner {
+    fn from(request: MultiSigRequestWithSigner) -> Self {
+        Self::V2(request)
+    }
 }
 
 #[derive(BorshStorageKey, BorshSerialize)]
@@ -60,6 +107,28 @@ pub enum StorageKeys {
     NumRequestsPk,
     FtCommittedBalances,
     EscrowTransfers,
+    ActionThresholds,
+    AmountTiers,
+    Rejections,
+    StakedBalances,
+    UnstakedBalances,
+    Tokens,
+    IncomingPayments,
+    IncomingPaymentIndex,
+    IncomingPaymentIndices {
+        token_id: AccountId,
+        sender_id: AccountId,
+    },
+    IncomingTotals,
+    ExecutedRequests,
+    History,
+    MemberHistory,
+    MemberHistoryIndices {
+        member: MultisigMember,
+    },
+    TokenBalances,
+    Budgets,
+    BudgetSpending,
 }
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -71,30 +140,65 @@ pub struct Contract {
     /// Latest request nonce.
     request_nonce: RequestId,
     /// All active requests.
-    requests: UnorderedMap<RequestId, MultiSigRequestWithSigner>,
-    /// All confirmations for active requests.
-    confirmations: LookupMap<RequestId, HashSet<String>>,
+    requests: UnorderedMap<RequestId, VersionedMultiSigRequestWithSigner>,
+    /// All confirmations for active requests, with the time each member confirmed.
+    confirmations: LookupMap<RequestId, HashMap<MultisigMember, u64>>,
+    /// Members who rejected active requests.
+    rejections: LookupMap<RequestId, HashSet<MultisigMember>>,
     /// Number of requests per member.
-    num_requests_pk: LookupMap<String, u32>,
+    num_requests_pk: LookupMap<MultisigMember, u32>,
     /// Limit number of active requests per member.
     active_requests_limit: u32,
+    /// Outcomes of executed requests.
+    executed_requests: LookupMap<RequestId, ExecutionOutcome>,
+    /// Append-only history of executed and deleted requests.
+    history: Vector<HistoryEntry>,
+    /// Indices in the history of the requests proposed or confirmed by each member.
+    member_history: LookupMap<MultisigMember, Vector<u64>>,
+    /// Time after which unconfirmed requests expire.
+    request_ttl: Option<u64>,
+    /// Timelock applied to high impact requests.
+    timelock_policy: Option<TimelockPolicy>,
+    /// Number of confirmations required per action type, overrides `num_confirmations`.
+    action_thresholds: UnorderedMap<ActionKind, u32>,
+    /// Amount tiers per token (`None` for NEAR), sorted by `min_amount`.
+    amount_tiers: UnorderedMap<Option<AccountId>, Vec<AmountTier>>,
 
     /// Payments
     ///
     /// Committed balance in escrow transfers.
     near_committed_balance: u128,
+    /// NEAR kept on top of storage staking, payments can't spend it.
+    storage_reserve: u128,
     /// Maps FT contracts to committed balance in escrow transfers.
     ft_committed_balance: UnorderedMap<AccountId, u128>,
 
     /// Pending escrow transfers.
-    escrow_transfers: UnorderedMap<CryptoHash, EscrowTransfer>,
-}
+    escrow_transfers: UnorderedMap<CryptoHash, VersionedEscrowTransfer>,
+    /// Registered token contracts with their cached metadata.
+    tokens: UnorderedMap<AccountId, Option<TokenMetadata>>,
+    /// Balances of registered tokens and of tokens with committed balances, cached from `ft_balance_of`.
+    token_balances: LookupMap<AccountId, CachedBalance>,
+    /// Ledger of FT payments received through `ft_transfer_call`.
+    incoming_payments: Vector<IncomingPayment>,
+    /// Positions in `incoming_payments` of the payments of each token and sender.
+    incoming_payment_index: LookupMap<(AccountId, AccountId), Vector<u64>>,
+    /// Total received per token through `ft_transfer_call`.
+    incoming_totals: LookupMap<AccountId, u128>,
+
+    /// Staking
+    ///
+    /// Maps staking pools to staked principal. Pools with unstaked balance only are kept with 0.
+    staked_balance: UnorderedMap<AccountId, u128>,
+    /// Maps staking pools to unstaked balance not withdrawn yet.
+    unstaked_balance: UnorderedMap<AccountId, u128>,
 
-#[inline]
-fn assert(condition: bool, error: &str) {
-    if !condition {
-        env::panic_str(error);
-    }
+    /// Budgets
+    ///
+    /// Spending caps of payments per category and token (`None` for NEAR).
+    budgets: UnorderedMap<BudgetKey, Budget>,
+    /// Amount spent from each budget in its current period.
+    budget_spending: LookupMap<BudgetKey, BudgetSpending>,
 }
 
 #[near_bindgen]
@@ -103,113 +207,197 @@ impl Contract {
     /// @params members: list of {"account_id": "name"} or {"public_key": "key"} members.
     /// @params num_confirmations: k of n signatures required to perform operations.
     #[init]
-    pub fn new(members: Vec<MultisigMember>, num_confirmations: u32) -> Self {
-        assert(
-            members.len() >= num_confirmations as usize,
-            "Members list must be equal or larger than number of confirmations",
-        );
+    #[handle_result]
+    pub fn new(
+        members: Vec<MultisigMember>,
+        num_confirmations: u32,
+    ) -> Result<Self, ContractError> {
+        if members.len() < num_confirmations as usize {
+            return Err(ContractError::InvalidNumConfirmations {
+                num_confirmations,
+                num_members: members.len() as u64,
+            });
+        }
         let mut multisig = Self {
             members: UnorderedSet::new(StorageKeys::Members),
             num_confirmations,
             request_nonce: 0,
             requests: UnorderedMap::new(StorageKeys::Requests),
             confirmations: LookupMap::new(StorageKeys::Confirmations),
+            rejections: LookupMap::new(StorageKeys::Rejections),
             num_requests_pk: LookupMap::new(StorageKeys::NumRequestsPk),
             active_requests_limit: ACTIVE_REQUESTS_LIMIT,
+            executed_requests: LookupMap::new(StorageKeys::ExecutedRequests),
+            history: Vector::new(StorageKeys::History),
+            member_history: LookupMap::new(StorageKeys::MemberHistory),
+            request_ttl: None,
+            timelock_policy: None,
+            action_thresholds: UnorderedMap::new(StorageKeys::ActionThresholds),
+            amount_tiers: UnorderedMap::new(StorageKeys::AmountTiers),
             near_committed_balance: 0,
+            storage_reserve: DEFAULT_STORAGE_RESERVE,
             ft_committed_balance: UnorderedMap::new(StorageKeys::FtCommittedBalances),
             escrow_transfers: UnorderedMap::new(StorageKeys::EscrowTransfers),
+            tokens: UnorderedMap::new(StorageKeys::Tokens),
+            token_balances: LookupMap::new(StorageKeys::TokenBalances),
+            incoming_payments: Vector::new(StorageKeys::IncomingPayments),
+            incoming_payment_index: LookupMap::new(StorageKeys::IncomingPaymentIndex),
+            incoming_totals: LookupMap::new(StorageKeys::IncomingTotals),
+            staked_balance: UnorderedMap::new(StorageKeys::StakedBalances),
+            unstaked_balance: UnorderedMap::new(StorageKeys::UnstakedBalances),
+            budgets: UnorderedMap::new(StorageKeys::Budgets),
+            budget_spending: LookupMap::new(StorageKeys::BudgetSpending),
         };
+        migration::write_state_version();
         let mut promise = Promise::new(env::current_account_id());
         for member in members {
             promise = multisig.add_member(promise, member);
         }
-        multisig
+        Ok(multisig)
     }
 
     /// Add request for multisig.
-    pub fn add_request(&mut self, request: MultiSigRequest) -> MultiSigResponse {
-        let current_member = self.current_member().unwrap_or_else(|| {
-            env::panic_str(
-                "Predecessor must be a member or transaction signed with key of given account",
+    #[handle_result]
+    pub fn add_request(
+        &mut self,
+        request: MultiSigRequest,
+    ) -> Result<MultiSigResponse, ContractError> {
+        let current_member = self.current_member().ok_or(ContractError::NotAMember)?;
+        for metadata in request
+            .actions
+            .iter()
+            .filter_map(MultiSigRequestAction::metadata)
+        {
+            check_payment_metadata(metadata)?;
+        }
+        // execution stops at the escrow, other actions would be charged to budgets and not executed
+        let has_escrow = request.actions.iter().any(|action| {
+            matches!(
+                action.kind(),
+                ActionKind::NearEscrowTransfer | ActionKind::FTEscrowTransfer
             )
         });
+        if has_escrow && request.actions.len() > 1 {
+            return Err(ContractError::NotSingleAction);
+        }
         // track how many requests this key has made
-        let num_requests = self
-            .num_requests_pk
-            .get(&current_member.to_string())
-            .unwrap_or(0)
-            + 1;
-        assert(
-            num_requests <= self.active_requests_limit,
-            "Account has too many active requests. Confirm or delete some.",
-        );
-        self.num_requests_pk
-            .insert(&current_member.to_string(), &num_requests);
+        let num_requests = self.num_requests_pk.get(&current_member).unwrap_or(0) + 1;
+        if num_requests > self.active_requests_limit {
+            return Err(ContractError::TooManyActiveRequests {
+                limit: self.active_requests_limit,
+            });
+        }
+        self.num_requests_pk.insert(&current_member, &num_requests);
+        PaymentsEvent::RequestAdded {
+            request_id: self.request_nonce,
+            member: current_member.clone(),
+            receiver_id: request.receiver_id.clone(),
+            actions: request.actions.iter().map(|action| action.kind()).collect(),
+        }
+        .emit();
         // add the request
         let request_added = MultiSigRequestWithSigner {
             member: current_member,
             added_timestamp: env::block_timestamp(),
+            executable_at: None,
             request,
         };
-        self.requests.insert(&self.request_nonce, &request_added);
-        let confirmations = HashSet::new();
+        self.requests
+            .insert(&self.request_nonce, &request_added.into());
+        let confirmations = HashMap::new();
         self.confirmations
             .insert(&self.request_nonce, &confirmations);
         self.request_nonce += 1;
-        MultiSigResponse::new(
+        Ok(MultiSigResponse::new(
             self.request_nonce - 1,
             FuncResponse::AddRequest(self.request_nonce - 1),
-        )
+        ))
     }
 
     /// Add request for multisig and confirm with the pk that added.
+    #[handle_result]
     pub fn add_request_and_confirm(
         &mut self,
         request: MultiSigRequest,
-    ) -> PromiseOrValue<MultiSigResponse> {
-        let request_id_resp = self.add_request(request);
+    ) -> Result<PromiseOrValue<MultiSigResponse>, ContractError> {
+        let request_id_resp = self.add_request(request)?;
         self.confirm(request_id_resp.request_id)
     }
 
     /// Remove given request and associated confirmations.
-    pub fn delete_request(&mut self, request_id: RequestId) {
-        self.assert_valid_request(request_id);
-        let request_with_signer = self
-            .requests
-            .get(&request_id)
-            .unwrap_or_else(|| env::panic_str("No such request"));
+    #[handle_result]
+    pub fn delete_request(&mut self, request_id: RequestId) -> Result<(), ContractError> {
+        self.check_valid_request(request_id)?;
+        let request_with_signer = self.get_request_with_signer(request_id)?;
         // can't delete requests before 15min
-        assert(
-            env::block_timestamp() > request_with_signer.added_timestamp + REQUEST_COOLDOWN,
-            "Request cannot be deleted immediately after creation.",
-        );
-        self.remove_request(request_id);
+        if env::block_timestamp() <= request_with_signer.added_timestamp + REQUEST_COOLDOWN {
+            return Err(ContractError::RequestCooldown);
+        }
+        self.discard_request(request_id, DeletionReason::Deleted);
+        Ok(())
+    }
+
+    /// Remove given expired request and associated confirmations. Can be called by anyone.
+    #[handle_result]
+    pub fn delete_expired_request(&mut self, request_id: RequestId) -> Result<(), ContractError> {
+        let request_with_signer = self.get_request_with_signer(request_id)?;
+        if !self.is_expired(&request_with_signer) {
+            return Err(ContractError::RequestNotExpired);
+        }
+        self.discard_request(request_id, DeletionReason::Expired);
+        Ok(())
     }
 
-    fn execute_request(&mut self, request: MultiSigRequest) -> PromiseOrValue<FuncResponse> {
+    fn execute_request(
+        &mut self,
+        request_id: RequestId,
+        request: MultiSigRequest,
+    ) -> Result<PromiseOrValue<FuncResponse>, ContractError> {
+        // check there is enough balance for all NEAR payments, accounting storage and committed balance
+        let requested = token_outflow(&request).get(&None).copied().unwrap_or(0);
+        let available = self.spendable_balance();
+        if requested > available {
+            return Err(ContractError::InsufficientBalance {
+                token_id: None,
+                available,
+                requested,
+            });
+        }
+        self.spend_budgets(&request)?;
         let mut promise = Promise::new(request.receiver_id.clone());
         let receiver_id = request.receiver_id.clone();
         let num_actions = request.actions.len();
         for action in request.actions {
+            if action.kind().is_config() {
+                PaymentsEvent::ConfigChanged {
+                    change: action.clone(),
+                }
+                .emit();
+            }
             promise = match action {
                 MultiSigRequestAction::CreateAccount => promise.create_account(),
                 MultiSigRequestAction::DeployContract { code } => {
-                    promise.deploy_contract(code.into())
+                    let promise = promise.deploy_contract(code.into());
+                    if receiver_id == env::current_account_id() {
+                        // upgrade the state to the layout of the new code in the same batch
+                        promise.function_call("migrate".to_string(), vec![], 0, MIGRATE_GAS)
+                    } else {
+                        promise
+                    }
                 }
                 MultiSigRequestAction::AddMember { member } => {
-                    self.assert_self_request(receiver_id.clone());
+                    self.check_self_request(&receiver_id)?;
                     self.add_member(promise, member)
                 }
                 MultiSigRequestAction::DeleteMember { member } => {
-                    self.assert_self_request(receiver_id.clone());
-                    self.delete_member(promise, member)
+                    self.check_self_request(&receiver_id)?;
+                    self.delete_member(promise, member)?
                 }
                 MultiSigRequestAction::AddKey {
                     public_key,
                     permission,
                 } => {
-                    self.assert_self_request(receiver_id.clone());
+                    self.check_self_request(&receiver_id)?;
                     if let Some(permission) = permission {
                         promise.add_access_key(
                             public_key,
@@ -225,110 +413,413 @@ impl Contract {
                         promise.add_full_access_key(public_key)
                     }
                 }
+                MultiSigRequestAction::DeleteKey { public_key } => {
+                    self.check_self_request(&receiver_id)?;
+                    if self.members.contains(&MultisigMember::AccessKey {
+                        public_key: public_key.clone(),
+                    }) {
+                        return Err(ContractError::KeyBelongsToMember);
+                    }
+                    promise.delete_key(public_key)
+                }
+                MultiSigRequestAction::DeleteAccount { beneficiary_id } => {
+                    self.check_self_request(&receiver_id)?;
+                    self.check_no_committed_balance()?;
+                    promise.delete_account(beneficiary_id)
+                }
                 // the following methods must be a single action
                 MultiSigRequestAction::SetNumConfirmations { num_confirmations } => {
-                    self.assert_one_action_only(receiver_id, num_actions);
+                    self.check_one_action_only(&receiver_id, num_actions)?;
                     self.num_confirmations = num_confirmations;
-                    return PromiseOrValue::Value(FuncResponse::Default(true));
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::SetActionThreshold {
+                    action,
+                    num_confirmations,
+                } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    match num_confirmations {
+                        Some(num_confirmations) => {
+                            self.check_num_confirmations(num_confirmations)?;
+                            self.action_thresholds.insert(&action, &num_confirmations);
+                        }
+                        None => {
+                            self.action_thresholds.remove(&action);
+                        }
+                    }
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::SetAmountTiers { token_id, tiers } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    if tiers.is_empty() {
+                        self.amount_tiers.remove(&token_id);
+                    } else {
+                        self.check_valid_tiers(&tiers)?;
+                        self.amount_tiers.insert(&token_id, &tiers);
+                    }
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
                 }
                 MultiSigRequestAction::SetActiveRequestsLimit {
                     active_requests_limit,
                 } => {
-                    self.assert_one_action_only(receiver_id, num_actions);
+                    self.check_one_action_only(&receiver_id, num_actions)?;
                     self.active_requests_limit = active_requests_limit;
-                    return PromiseOrValue::Value(FuncResponse::Default(true));
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::SetRequestTtl { request_ttl } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    if request_ttl == Some(U64(0)) {
+                        return Err(ContractError::InvalidRequestTtl);
+                    }
+                    self.request_ttl = request_ttl.map(|ttl| ttl.0);
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::SetTimelockPolicy { policy } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    self.timelock_policy = policy;
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::SetStorageReserve { storage_reserve } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    self.storage_reserve = storage_reserve.0;
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::SetBudget {
+                    category,
+                    token_id,
+                    budget,
+                } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    self.set_budget(category, token_id, budget)?;
+                    return Ok(PromiseOrValue::Value(FuncResponse::Default(true)));
+                }
+                MultiSigRequestAction::RegisterToken { token_id } => {
+                    self.check_one_action_only(&receiver_id, num_actions)?;
+                    self.register_token(request_id, token_id)
+                }
+                MultiSigRequestAction::UnregisterToken { token_id } => {
+                    self.check_self_request(&receiver_id)?;
+                    self.unregister_token(token_id)?;
+                    promise
                 }
 
                 // Payments
-                MultiSigRequestAction::Transfer { amount } => {
-                    // check if there is enough balance accounuting commited balance
-                    let available: u128 = env::account_balance() - self.near_committed_balance;
-
-                    assert!(
-                        amount.0 <= available,
-                        "Not enough balance to transfer. Available: {}, requested: {}",
-                        available,
-                        amount.0
-                    );
-
-                    promise.transfer(amount.into())
-                }
+                MultiSigRequestAction::Transfer { amount, .. } => promise.transfer(amount.into()),
                 MultiSigRequestAction::NearEscrowTransfer {
                     receiver_id,
                     amount,
-                    label,
+                    metadata,
                     is_cancellable,
                 } => {
-                    let res = self.create_near_escrow_payment(
+                    let id = self.create_near_escrow_payment(
                         receiver_id,
                         amount.into(),
-                        label,
+                        metadata,
                         is_cancellable,
-                    );
-                    let id =
-                        res.unwrap_or_else(|_| env::panic_str("Failed to create escrow payment"));
-                    return PromiseOrValue::Value(FuncResponse::EscrowPayment(id.into()));
+                    )?;
+                    return Ok(PromiseOrValue::Value(FuncResponse::EscrowPayment(
+                        id.into(),
+                    )));
                 }
                 MultiSigRequestAction::FTEscrowTransfer {
                     receiver_id,
                     amount,
                     token_id,
-                    label,
+                    metadata,
                     is_cancellable,
-                } => ext_nep141_token::ext(token_id.clone())
-                    .ft_balance_of(env::current_account_id())
-                    .then(
-                        Self::ext(env::current_account_id()).callback_create_ft_escrow(
-                            receiver_id,
-                            amount.into(),
-                            label,
-                            is_cancellable,
-                            token_id,
-                        ),
-                    ),
+                } => {
+                    self.check_registered_token(&token_id)?;
+                    ext_nep141_token::ext(token_id.clone())
+                        .ft_balance_of(env::current_account_id())
+                        .then(
+                            Self::ext(env::current_account_id()).callback_create_ft_escrow(
+                                receiver_id,
+                                amount.into(),
+                                metadata,
+                                is_cancellable,
+                                token_id,
+                            ),
+                        )
+                }
+
+                // Staking, the staking pool calls must be a single action
+                action @ (MultiSigRequestAction::DepositAndStake { .. }
+                | MultiSigRequestAction::Unstake { .. }
+                | MultiSigRequestAction::UnstakeAll
+                | MultiSigRequestAction::Withdraw { .. }) => {
+                    if num_actions != 1 {
+                        return Err(ContractError::NotSingleAction);
+                    }
+                    self.staking_promise(request_id, receiver_id.clone(), action)?
+                }
             };
         }
-        promise.into()
+        Ok(promise.into())
     }
 
     /// Confirm given request with given signing key.
     /// If with this, there has been enough confirmation, a promise with request will be scheduled.
-    pub fn confirm(&mut self, request_id: RequestId) -> PromiseOrValue<MultiSigResponse> {
-        self.assert_valid_request(request_id);
-        let member = self
-            .current_member()
-            .unwrap_or_else(|| env::panic_str("Must be validated above"));
+    #[handle_result]
+    pub fn confirm(
+        &mut self,
+        request_id: RequestId,
+    ) -> Result<PromiseOrValue<MultiSigResponse>, ContractError> {
+        let member = self.check_valid_request(request_id)?;
         let mut confirmations = self.confirmations.get(&request_id).unwrap();
-        assert(
-            !confirmations.contains(&member.to_string()),
-            "Already confirmed this request with this key",
-        );
-        if confirmations.len() as u32 + 1 >= self.num_confirmations {
-            let request = self.remove_request(request_id);
-            /********************************
-            NOTE: If the tx execution fails for any reason, the request and confirmations are removed already, so the client has to start all over
-            ********************************/
-            let ret = self.execute_request(request);
-            match ret {
-                PromiseOrValue::Promise(p) => p.into(),
-                PromiseOrValue::Value(v) => {
-                    PromiseOrValue::Value(MultiSigResponse::new(request_id, v))
+        if confirmations.contains_key(&member) {
+            return Err(ContractError::AlreadyConfirmed);
+        }
+        if self.get_rejection_set(request_id).contains(&member) {
+            return Err(ContractError::AlreadyRejected);
+        }
+        let mut request_with_signer = self.request_with_signer(request_id).unwrap();
+        if request_with_signer.executable_at.is_some() {
+            return Err(ContractError::RequestQueued);
+        }
+        if self.is_expired(&request_with_signer) {
+            return Err(ContractError::RequestExpired);
+        }
+        PaymentsEvent::RequestConfirmed {
+            request_id,
+            member: member.clone(),
+        }
+        .emit();
+        let num_confirmations = self.request_threshold(&request_with_signer.request);
+        confirmations.insert(member.clone(), env::block_timestamp());
+        self.confirmations.insert(&request_id, &confirmations);
+        if confirmations.len() as u32 >= num_confirmations {
+            if let Some(duration) = self.timelock_duration(&request_with_signer.request) {
+                // queue the request, it is executed with `execute` once the timelock has passed
+                let executable_at = env::block_timestamp() + duration;
+                request_with_signer.executable_at = Some(executable_at);
+                self.requests
+                    .insert(&request_id, &request_with_signer.into());
+                PaymentsEvent::RequestQueued {
+                    request_id,
+                    executable_at: executable_at.into(),
                 }
+                .emit();
+                return Ok(PromiseOrValue::Value(MultiSigResponse::new(
+                    request_id,
+                    FuncResponse::Queued(executable_at.into()),
+                )));
             }
+            self.execute_confirmed_request(request_id)
         } else {
-            confirmations.insert(member.to_string());
-            self.confirmations.insert(&request_id, &confirmations);
-            PromiseOrValue::Value(MultiSigResponse::new(
+            Ok(PromiseOrValue::Value(MultiSigResponse::new(
                 request_id,
                 FuncResponse::Default(true),
-            ))
+            )))
+        }
+    }
+
+    /// Revoke the confirmation given to a request by the caller.
+    #[handle_result]
+    pub fn revoke_confirmation(&mut self, request_id: RequestId) -> Result<(), ContractError> {
+        let member = self.check_valid_request(request_id)?;
+        if self
+            .request_with_signer(request_id)
+            .unwrap()
+            .executable_at
+            .is_some()
+        {
+            return Err(ContractError::RequestQueued);
+        }
+        let mut confirmations = self.confirmations.get(&request_id).unwrap();
+        if confirmations.remove(&member).is_none() {
+            return Err(ContractError::NotConfirmed);
+        }
+        self.confirmations.insert(&request_id, &confirmations);
+        PaymentsEvent::ConfirmationRevoked { request_id, member }.emit();
+        Ok(())
+    }
+
+    /// Reject given request with the caller's key.
+    /// If the remaining members can no longer reach the required confirmations, the request is closed.
+    #[handle_result]
+    pub fn reject(&mut self, request_id: RequestId) -> Result<(), ContractError> {
+        let member = self.check_valid_request(request_id)?;
+        let request_with_signer = self.request_with_signer(request_id).unwrap();
+        if request_with_signer.executable_at.is_some() {
+            return Err(ContractError::RequestQueued);
+        }
+        if self
+            .confirmations
+            .get(&request_id)
+            .unwrap()
+            .contains_key(&member)
+        {
+            return Err(ContractError::ConfirmationNotRevoked);
+        }
+        let mut rejections = self.get_rejection_set(request_id);
+        if !rejections.insert(member.clone()) {
+            return Err(ContractError::AlreadyRejected);
+        }
+        let num_confirmations = self.request_threshold(&request_with_signer.request);
+        // rejections of deleted members are ignored
+        let num_rejections = rejections
+            .iter()
+            .filter(|member| self.members.contains(member))
+            .count() as u64;
+        let closed = self.members.len().saturating_sub(num_rejections) < num_confirmations as u64;
+        PaymentsEvent::RequestRejected {
+            request_id,
+            member,
+            closed,
+        }
+        .emit();
+        if closed {
+            self.discard_request(request_id, DeletionReason::Rejected);
+        } else {
+            self.rejections.insert(&request_id, &rejections);
+        }
+        Ok(())
+    }
+
+    /// Execute given queued request once its timelock has passed.
+    #[handle_result]
+    pub fn execute(
+        &mut self,
+        request_id: RequestId,
+    ) -> Result<PromiseOrValue<MultiSigResponse>, ContractError> {
+        self.check_valid_request(request_id)?;
+        let executable_at = self
+            .request_with_signer(request_id)
+            .unwrap()
+            .executable_at
+            .ok_or(ContractError::RequestNotQueued)?;
+        if env::block_timestamp() < executable_at {
+            return Err(ContractError::TimelockNotPassed { executable_at });
         }
+        self.execute_confirmed_request(request_id)
+    }
+
+    /// Veto given queued request before it is executed, removing it.
+    #[handle_result]
+    pub fn veto(&mut self, request_id: RequestId) -> Result<(), ContractError> {
+        self.check_valid_request(request_id)?;
+        if self
+            .request_with_signer(request_id)
+            .unwrap()
+            .executable_at
+            .is_none()
+        {
+            return Err(ContractError::RequestNotQueued);
+        }
+        self.discard_request(request_id, DeletionReason::Vetoed);
+        Ok(())
     }
 
     /********************************
     Helper methods
     ********************************/
 
+    /// Removes a fully confirmed request and executes it.
+    fn execute_confirmed_request(
+        &mut self,
+        request_id: RequestId,
+    ) -> Result<PromiseOrValue<MultiSigResponse>, ContractError> {
+        let (request, entry) = self.remove_request(request_id);
+        /********************************
+        NOTE: If the tx execution fails for any reason, the request and confirmations are removed already, so the client has to start all over.
+        The outcome is recorded in executed_requests and the history by callback_request_executed.
+        ********************************/
+        let ret = self.execute_request(request_id, request)?;
+        let history_index = self.archive_request(&entry);
+        Ok(match ret {
+            PromiseOrValue::Promise(p) => p
+                .then(
+                    Self::ext(env::current_account_id())
+                        .callback_request_executed(request_id, history_index),
+                )
+                .into(),
+            PromiseOrValue::Value(v) => {
+                PromiseOrValue::Value(self.record_execution(request_id, history_index, true, v)?)
+            }
+        })
+    }
+
+    /// Stores the outcome of an executed request and returns the matching response.
+    fn record_execution(
+        &mut self,
+        request_id: RequestId,
+        history_index: u64,
+        success: bool,
+        response: FuncResponse,
+    ) -> Result<MultiSigResponse, ContractError> {
+        let entry = self.update_archived_request(history_index, success, &response)?;
+        if !success {
+            // the budgets were charged when the request was removed to be executed
+            self.refund_budgets(&entry.payments(), entry.finished_at.0);
+        }
+        let outcome = ExecutionOutcome {
+            success,
+            response,
+            executed_at: env::block_timestamp().into(),
+        };
+        self.executed_requests.insert(&request_id, &outcome);
+        PaymentsEvent::RequestExecuted {
+            request_id,
+            success,
+        }
+        .emit();
+        Ok(MultiSigResponse::new(request_id, outcome.response))
+    }
+
+    /// Returns NEAR that can be paid out: the balance minus storage staking, the storage reserve
+    /// and the balance committed in escrow transfers.
+    fn spendable_balance(&self) -> u128 {
+        env::account_balance()
+            .saturating_sub(env::storage_usage() as u128 * env::storage_byte_cost())
+            .saturating_sub(self.storage_reserve)
+            .saturating_sub(self.near_committed_balance)
+    }
+
+    /// Returns total amount requested by active requests per token (`None` for NEAR).
+    fn pending_outflow(&self) -> HashMap<Option<AccountId>, u128> {
+        let mut pending: HashMap<Option<AccountId>, u128> = HashMap::new();
+        for (_, request_with_signer) in self.requests_with_signer() {
+            if self.is_expired(&request_with_signer) {
+                continue;
+            }
+            for (token_id, amount) in token_outflow(&request_with_signer.request) {
+                *pending.entry(token_id).or_insert(0) += amount;
+            }
+        }
+        pending
+    }
+
+    /// Returns the timelock duration if given request is high impact and a timelock policy is set.
+    fn timelock_duration(&self, request: &MultiSigRequest) -> Option<u64> {
+        let policy = self.timelock_policy.as_ref()?;
+        let is_high_impact = request.actions.iter().any(|action| {
+            matches!(
+                action.kind(),
+                ActionKind::AddMember
+                    | ActionKind::DeleteMember
+                    | ActionKind::AddKey
+                    | ActionKind::DeleteKey
+                    | ActionKind::DeleteAccount
+                    | ActionKind::DeployContract
+                    | ActionKind::SetNumConfirmations
+                    | ActionKind::SetActionThreshold
+                    | ActionKind::SetAmountTiers
+                    | ActionKind::SetTimelockPolicy
+            )
+        }) || token_outflow(request).iter().any(|(token_id, amount)| {
+            match token_id {
+                None => *amount >= policy.transfer_threshold.0,
+                // the threshold is in NEAR, any amount of a token is timelocked
+                Some(_) => *amount > 0,
+            }
+        });
+        if is_high_impact {
+            Some(policy.duration.0)
+        } else {
+            None
+        }
+    }
+
     /// Returns current member: either predecessor as account or if it's the same as current account - signer.
     fn current_member(&self) -> Option<MultisigMember> {
         let member = if env::current_account_id() == env::predecessor_account_id() {
@@ -350,6 +841,10 @@ impl Contract {
     /// Add member to the list. Adds access key if member is key based.
     fn add_member(&mut self, promise: Promise, member: MultisigMember) -> Promise {
         self.members.insert(&member);
+        PaymentsEvent::MemberAdded {
+            member: member.clone(),
+        }
+        .emit();
         match member {
             MultisigMember::AccessKey { public_key } => promise.add_access_key(
                 public_key,
@@ -362,91 +857,280 @@ impl Contract {
     }
 
     /// Delete member from the list. Removes access key if the member is key based.
-    fn delete_member(&mut self, promise: Promise, member: MultisigMember) -> Promise {
-        assert(
-            self.members.len() > self.num_confirmations as u64,
-            "Removing given member will make total number of members below number of confirmations",
-        );
+    fn delete_member(
+        &mut self,
+        promise: Promise,
+        member: MultisigMember,
+    ) -> Result<Promise, ContractError> {
+        if self.members.len() <= self.max_threshold() as u64 {
+            return Err(ContractError::TooFewMembers);
+        }
         // delete outstanding requests by public_key
         let request_ids: Vec<u32> = self
-            .requests
-            .iter()
+            .requests_with_signer()
             .filter_map(|(k, r)| if r.member == member { Some(k) } else { None })
             .collect();
         for request_id in request_ids {
-            // remove confirmations for this request
-            self.confirmations.remove(&request_id);
-            self.requests.remove(&request_id);
+            self.discard_request(request_id, DeletionReason::MemberRemoved);
         }
         // remove num_requests_pk entry for member
-        self.num_requests_pk.remove(&member.to_string());
+        self.num_requests_pk.remove(&member);
         self.members.remove(&member);
-        match member {
+        PaymentsEvent::MemberRemoved {
+            member: member.clone(),
+        }
+        .emit();
+        Ok(match member {
             MultisigMember::AccessKey { public_key } => promise.delete_key(public_key),
             MultisigMember::Account { account_id: _ } => promise,
-        }
+        })
+    }
+
+    /// Returns given active request, fails if there is no such request.
+    fn get_request_with_signer(
+        &self,
+        request_id: RequestId,
+    ) -> Result<MultiSigRequestWithSigner, ContractError> {
+        self.request_with_signer(request_id)
+            .ok_or(ContractError::RequestNotFound(request_id))
+    }
+
+    /// Returns given active request, converted to the current layout.
+    fn request_with_signer(&self, request_id: RequestId) -> Option<MultiSigRequestWithSigner> {
+        self.requests.get(&request_id).map(Into::into)
+    }
+
+    /// Iterates over active requests, converted to the current layout.
+    fn requests_with_signer(
+        &self,
+    ) -> impl Iterator<Item = (RequestId, MultiSigRequestWithSigner)> + '_ {
+        self.requests
+            .iter()
+            .map(|(request_id, request)| (request_id, request.into()))
+    }
+
+    /// Returns confirmations of given active request, fails if there is no such request.
+    fn get_confirmation_map(
+        &self,
+        request_id: RequestId,
+    ) -> Result<HashMap<MultisigMember, u64>, ContractError> {
+        self.confirmations
+            .get(&request_id)
+            .ok_or(ContractError::RequestNotFound(request_id))
+    }
+
+    /// Returns members who rejected given request.
+    fn get_rejection_set(&self, request_id: RequestId) -> HashSet<MultisigMember> {
+        self.rejections.get(&request_id).unwrap_or_default()
+    }
+
+    /// Returns true if the request is older than the request TTL. Queued requests do not expire.
+    fn is_expired(&self, request_with_signer: &MultiSigRequestWithSigner) -> bool {
+        request_with_signer.executable_at.is_none()
+            && self.request_ttl.is_some_and(|ttl| {
+                env::block_timestamp() > request_with_signer.added_timestamp.saturating_add(ttl)
+            })
+    }
+
+    /// Returns number of confirmations required for given request: the strictest threshold among its actions
+    /// and the amount tiers matching its outflow.
+    fn request_threshold(&self, request: &MultiSigRequest) -> u32 {
+        let action_threshold = request
+            .actions
+            .iter()
+            .map(|action| {
+                self.action_thresholds
+                    .get(&action.kind())
+                    .unwrap_or(self.num_confirmations)
+            })
+            .max()
+            .unwrap_or(self.num_confirmations);
+        token_outflow(request)
+            .into_iter()
+            .filter_map(|(token_id, amount)| self.tier_threshold(&token_id, amount))
+            .fold(action_threshold, u32::max)
+    }
+
+    /// Returns number of confirmations required by the highest tier reached by given amount of a token.
+    fn tier_threshold(&self, token_id: &Option<AccountId>, amount: u128) -> Option<u32> {
+        self.amount_tiers
+            .get(token_id)?
+            .into_iter()
+            .rev()
+            .find(|tier| amount >= tier.min_amount.0)
+            .map(|tier| match tier.required {
+                RequiredConfirmations::Count(num_confirmations) => num_confirmations,
+                RequiredConfirmations::AllMembers => self.members.len() as u32,
+            })
+    }
+
+    /// Returns the highest number of confirmations any request can require.
+    fn max_threshold(&self) -> u32 {
+        let tier_max = self
+            .amount_tiers
+            .values()
+            .flatten()
+            .filter_map(|tier| match tier.required {
+                RequiredConfirmations::Count(num_confirmations) => Some(num_confirmations),
+                RequiredConfirmations::AllMembers => None,
+            })
+            .max()
+            .unwrap_or(0);
+        self.action_thresholds
+            .values()
+            .max()
+            .unwrap_or(0)
+            .max(tier_max)
+            .max(self.num_confirmations)
+    }
+
+    /// Removes request without executing it and archives it in the history.
+    fn discard_request(&mut self, request_id: RequestId, reason: DeletionReason) {
+        let (_, mut entry) = self.remove_request(request_id);
+        entry.status = RequestStatus::Deleted {
+            reason: reason.clone(),
+        };
+        self.archive_request(&entry);
+        PaymentsEvent::RequestDeleted { request_id, reason }.emit();
     }
 
     /// Removes request, removes confirmations and reduces num_requests_pk - used in delete, delete_key, and confirm
-    fn remove_request(&mut self, request_id: RequestId) -> MultiSigRequest {
-        // remove confirmations for this request
-        self.confirmations.remove(&request_id);
+    /// Returns the request and its history entry waiting for execution.
+    fn remove_request(&mut self, request_id: RequestId) -> (MultiSigRequest, HistoryEntry) {
+        // remove confirmations and rejections for this request
+        let confirmations = self.confirmations.remove(&request_id).unwrap_or_default();
+        self.rejections.remove(&request_id);
         // remove the original request
-        let request_with_signer = self
+        let request_with_signer: MultiSigRequestWithSigner = self
             .requests
             .remove(&request_id)
+            .map(Into::into)
             .unwrap_or_else(|| env::panic_str("Failed to remove existing element"));
         // decrement num_requests for original request signer
-        let original_member = request_with_signer.member;
-        let mut num_requests = self
-            .num_requests_pk
-            .get(&original_member.to_string())
-            .unwrap_or(0);
+        let original_member = request_with_signer.member.clone();
+        let mut num_requests = self.num_requests_pk.get(&original_member).unwrap_or(0);
         // safety check for underrun (unlikely since original_signer_pk must have num_requests_pk > 0)
         num_requests = num_requests.saturating_sub(1);
-        self.num_requests_pk
-            .insert(&original_member.to_string(), &num_requests);
+        self.num_requests_pk.insert(&original_member, &num_requests);
         // return request
-        request_with_signer.request
+        let entry = HistoryEntry {
+            request_id,
+            request: request_with_signer.request.clone().without_code(),
+            proposer: request_with_signer.member,
+            confirmations: to_confirmations(confirmations),
+            added_at: request_with_signer.added_timestamp.into(),
+            finished_at: env::block_timestamp().into(),
+            status: RequestStatus::Executing,
+            payment_ids: vec![],
+        };
+        (request_with_signer.request, entry)
     }
 
     /// Prevents access to calling requests and make sure request_id is valid - used in delete and confirm
-    fn assert_valid_request(&mut self, request_id: RequestId) {
+    /// Returns the calling member.
+    fn check_valid_request(&self, request_id: RequestId) -> Result<MultisigMember, ContractError> {
         // request must come from key added to contract account
-        assert(
-            self.current_member().is_some(),
-            "Caller (predecessor or signer) is not a member of this multisig",
-        );
-        // request must exist
-        assert(
-            self.requests.get(&request_id).is_some(),
-            "No such request: either wrong number or already confirmed",
-        );
-        // request must have
-        assert(
-            self.confirmations.get(&request_id).is_some(),
-            "Internal error: confirmations mismatch requests",
-        );
+        let member = self.current_member().ok_or(ContractError::NotAMember)?;
+        // request must exist, with its confirmations
+        if self.requests.get(&request_id).is_none() || self.confirmations.get(&request_id).is_none()
+        {
+            return Err(ContractError::RequestNotFound(request_id));
+        }
+        Ok(member)
+    }
+
+    /// Makes sure given number of confirmations can be reached by the members
+    fn check_num_confirmations(&self, num_confirmations: u32) -> Result<(), ContractError> {
+        if num_confirmations == 0 || self.members.len() < num_confirmations as u64 {
+            return Err(ContractError::InvalidNumConfirmations {
+                num_confirmations,
+                num_members: self.members.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Makes sure tiers are sorted by strictly increasing amount and can be reached by the members
+    fn check_valid_tiers(&self, tiers: &[AmountTier]) -> Result<(), ContractError> {
+        if !tiers
+            .windows(2)
+            .all(|w| w[0].min_amount.0 < w[1].min_amount.0)
+        {
+            return Err(ContractError::InvalidAmountTiers);
+        }
+        for tier in tiers {
+            if let RequiredConfirmations::Count(num_confirmations) = tier.required {
+                self.check_num_confirmations(num_confirmations)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prevents deleting the multisig account while funds are owed or held elsewhere
+    fn check_no_committed_balance(&self) -> Result<(), ContractError> {
+        if !self.escrow_transfers.is_empty() {
+            return Err(ContractError::PendingEscrowTransfers);
+        }
+        if self.near_committed_balance != 0
+            || self
+                .ft_committed_balance
+                .values()
+                .any(|balance| balance != 0)
+        {
+            return Err(ContractError::CommittedBalance);
+        }
+        if !self.staked_balance.is_empty() {
+            return Err(ContractError::StakedBalance);
+        }
+        Ok(())
     }
 
     /// Prevents request from approving tx on another account
-    fn assert_self_request(&mut self, receiver_id: AccountId) {
-        assert(
-            receiver_id == env::current_account_id(),
-            "This method only works when receiver_id is equal to current_account_id",
-        );
+    fn check_self_request(&self, receiver_id: &AccountId) -> Result<(), ContractError> {
+        if receiver_id != &env::current_account_id() {
+            return Err(ContractError::NotSelfRequest);
+        }
+        Ok(())
     }
 
     /// Prevents a request from being bundled with other actions
-    fn assert_one_action_only(&mut self, receiver_id: AccountId, num_actions: usize) {
-        self.assert_self_request(receiver_id);
-        assert(num_actions == 1, "This method should be a separate request");
+    fn check_one_action_only(
+        &self,
+        receiver_id: &AccountId,
+        num_actions: usize,
+    ) -> Result<(), ContractError> {
+        self.check_self_request(receiver_id)?;
+        if num_actions != 1 {
+            return Err(ContractError::NotSingleAction);
+        }
+        Ok(())
     }
 
     /*******
      * Callback functions
      */
 
+    /// Records the outcome of the promise built by `execute_request`.
+    /// Responses of the payment callbacks are passed through with the right request id.
+    #[private]
+    #[handle_result]
+    pub fn callback_request_executed(
+        &mut self,
+        request_id: RequestId,
+        history_index: u64,
+    ) -> Result<MultiSigResponse, ContractError> {
+        let (success, response) = match env::promise_result(0) {
+            PromiseResult::Successful(value) => (
+                true,
+                serde_json::from_slice::<MultiSigResponse>(&value)
+                    .map(|r| r.response)
+                    .unwrap_or(FuncResponse::Default(true)),
+            ),
+            _ => (false, FuncResponse::Default(false)),
+        };
+        self.record_execution(request_id, history_index, success, response)
+    }
+
     /********************************
     View methods
     ********************************/
@@ -456,48 +1140,221 @@ impl Contract {
         self.members.to_vec()
     }
 
-    pub fn get_request(&self, request_id: RequestId) -> MultiSigRequest {
-        (self
-            .requests
-            .get(&request_id)
-            .unwrap_or_else(|| env::panic_str("No such request")))
-        .request
+    #[handle_result]
+    pub fn get_request(&self, request_id: RequestId) -> Result<MultiSigRequest, ContractError> {
+        Ok(self.get_request_with_signer(request_id)?.request)
     }
 
     pub fn get_num_requests_per_member(&self, member: MultisigMember) -> u32 {
-        self.num_requests_pk.get(&member.to_string()).unwrap_or(0)
+        self.num_requests_pk.get(&member).unwrap_or(0)
     }
 
+    /// Returns ids of requests that can still be confirmed.
     pub fn list_request_ids(&self) -> Vec<RequestId> {
-        self.requests.keys().collect()
+        self.requests_with_signer()
+            .filter_map(|(k, r)| if self.is_expired(&r) { None } else { Some(k) })
+            .collect()
     }
 
-    pub fn get_confirmations(&self, request_id: RequestId) -> Vec<String> {
-        self.confirmations
-            .get(&request_id)
-            .unwrap_or_else(|| env::panic_str("No such request"))
-            .into_iter()
+    /// Returns ids of expired requests, waiting to be deleted.
+    pub fn list_expired_request_ids(&self) -> Vec<RequestId> {
+        self.requests_with_signer()
+            .filter_map(|(k, r)| if self.is_expired(&r) { Some(k) } else { None })
             .collect()
     }
 
+    /// Returns ids of requests waiting for their timelock, with the time they become executable.
+    pub fn list_queued_requests(&self) -> Vec<(RequestId, U64)> {
+        self.requests_with_signer()
+            .filter_map(|(k, r)| r.executable_at.map(|t| (k, t.into())))
+            .collect()
+    }
+
+    /// Returns the timelock policy, if any.
+    pub fn get_timelock_policy(&self) -> Option<TimelockPolicy> {
+        self.timelock_policy.clone()
+    }
+
+    /// Returns the request TTL in nanoseconds, if any.
+    pub fn get_request_ttl(&self) -> Option<U64> {
+        self.request_ttl.map(U64)
+    }
+
+    #[handle_result]
+    pub fn get_confirmations(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Vec<MultisigMember>, ContractError> {
+        Ok(self.get_confirmation_map(request_id)?.into_keys().collect())
+    }
+
+    /// Returns members who rejected given request.
+    #[handle_result]
+    pub fn get_rejections(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Vec<MultisigMember>, ContractError> {
+        self.get_request_with_signer(request_id)?;
+        Ok(self.get_rejection_set(request_id).into_iter().collect())
+    }
+
+    /// Returns members who confirmed given request and when they did.
+    #[handle_result]
+    pub fn get_confirmations_with_timestamps(
+        &self,
+        request_id: RequestId,
+    ) -> Result<Vec<Confirmation>, ContractError> {
+        Ok(to_confirmations(self.get_confirmation_map(request_id)?))
+    }
+
     pub fn get_num_confirmations(&self) -> u32 {
         self.num_confirmations
     }
 
+    /// Returns number of confirmations required per action type.
+    pub fn get_action_thresholds(&self) -> Vec<(ActionKind, u32)> {
+        self.action_thresholds.to_vec()
+    }
+
+    /// Returns amount tiers per token, `None` being NEAR.
+    pub fn get_amount_tiers(&self) -> Vec<(Option<AccountId>, Vec<AmountTier>)> {
+        self.amount_tiers.to_vec()
+    }
+
+    /// Returns number of confirmations required to execute given request.
+    #[handle_result]
+    pub fn get_request_threshold(&self, request_id: RequestId) -> Result<u32, ContractError> {
+        let request_with_signer = self.get_request_with_signer(request_id)?;
+        Ok(self.request_threshold(&request_with_signer.request))
+    }
+
+    /// Returns the outcome of given executed request, `None` if it was not executed (yet).
+    pub fn get_execution_outcome(&self, request_id: RequestId) -> Option<ExecutionOutcome> {
+        self.executed_requests.get(&request_id)
+    }
+
     pub fn get_request_nonce(&self) -> u32 {
         self.request_nonce
     }
+
+    /// Returns NEAR that can be paid out, accounting storage staking, the storage reserve and escrow transfers.
+    pub fn get_spendable_balance(&self) -> U128 {
+        self.spendable_balance().into()
+    }
+
+    /// Returns the whole configuration of the multisig.
+    pub fn get_config(&self) -> ContractConfig {
+        ContractConfig {
+            num_confirmations: self.num_confirmations,
+            active_requests_limit: self.active_requests_limit,
+            request_cooldown: REQUEST_COOLDOWN.into(),
+            num_members: self.members.len(),
+            request_ttl: self.get_request_ttl(),
+            timelock_policy: self.get_timelock_policy(),
+            action_thresholds: self.get_action_thresholds(),
+            amount_tiers: self.get_amount_tiers(),
+            storage_reserve: self.storage_reserve.into(),
+            state_version: STATE_VERSION,
+        }
+    }
+
+    /// NEP-330: returns the version of the deployed code and where to find its source.
+    pub fn contract_source_metadata(&self) -> ContractSourceMetadata {
+        ContractSourceMetadata {
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            link: Some(env!("CARGO_PKG_REPOSITORY").to_string()),
+            standards: vec![
+                Standard {
+                    standard: "nep330".to_string(),
+                    version: "1.1.0".to_string(),
+                },
+                Standard {
+                    standard: "nep297".to_string(),
+                    version: "1.0.0".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+/// Converts stored confirmations to `Confirmation`s.
+fn to_confirmations(confirmations: HashMap<MultisigMember, u64>) -> Vec<Confirmation> {
+    confirmations
+        .into_iter()
+        .map(|(member, timestamp)| Confirmation {
+            member,
+            timestamp: timestamp.into(),
+        })
+        .collect()
+}
+
+/// Returns total amount leaving the contract per token (`None` for NEAR) if given request is executed.
+fn token_outflow(request: &MultiSigRequest) -> HashMap<Option<AccountId>, u128> {
+    request_outflow(&request.payments(), |payment| {
+        Some(payment.token_id.clone())
+    })
+}
+
+/// Returns total amount of the payments of a request per key, payments without key are skipped.
+pub(crate) fn request_outflow<K: Eq + std::hash::Hash>(
+    payments: &[PaymentSummary],
+    key: impl Fn(&PaymentSummary) -> Option<K>,
+) -> HashMap<K, u128> {
+    let mut outflow: HashMap<K, u128> = HashMap::new();
+    for payment in payments {
+        let key = match key(payment) {
+            Some(key) => key,
+            None => continue,
+        };
+        let total = outflow.entry(key).or_insert(0);
+        *total = total.saturating_add(payment.amount.0);
+    }
+    outflow
+}
+
+/// Keeps payment metadata within the size limits, it is stored with the request and the escrow transfer.
+/// Payments must also name the category of their budget.
+fn check_payment_metadata(metadata: &PaymentMetadata) -> Result<(), ContractError> {
+    let fields = [
+        ("label", &metadata.label),
+        ("invoice_number", &metadata.invoice_number),
+        ("category", &metadata.category),
+        ("external_reference", &metadata.external_reference),
+    ];
+    for (field, value) in fields {
+        if value.as_ref().map_or(0, String::len) > MAX_METADATA_FIELD_LEN as usize {
+            return Err(ContractError::MetadataTooLong {
+                field: field.to_string(),
+                max_len: MAX_METADATA_FIELD_LEN,
+            });
+        }
+    }
+    if metadata.tags.len() > MAX_TAGS as usize {
+        return Err(ContractError::TooManyTags { max_tags: MAX_TAGS });
+    }
+    if metadata
+        .tags
+        .iter()
+        .any(|tag| tag.len() > MAX_TAG_LEN as usize)
+    {
+        return Err(ContractError::MetadataTooLong {
+            field: "tags".to_string(),
+            max_len: MAX_TAG_LEN,
+        });
+    }
+    budgets::check_category(metadata.category.as_ref())
 }
 
 #[cfg(test)]
 mod tests {
-    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
     use near_sdk::Balance;
-    use near_sdk::{testing_env, PublicKey};
+    use near_sdk::{testing_env, PublicKey, RuntimeFeesConfig, VMConfig};
     use near_sdk::{AccountId, VMContext};
     use std::convert::TryFrom;
 
     use super::*;
+    use crate::migration::{ContractV1, MultiSigRequestActionV1, MultiSigRequestWithSignerV1};
 
     pub fn alice() -> AccountId {
         AccountId::new_unchecked("alice".to_string())
@@ -511,6 +1368,17 @@ mod tests {
         181, 38, 90, 244, 111, 207, 37, 216, 79, 84, 50, 83, 164,
     ];
 
+    /// Metadata of test payments, charged to the `operations` budget.
+    fn payment_metadata() -> PaymentMetadata {
+        PaymentMetadata {
+            category: Some("operations".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Balance covering storage staking and the storage reserve, so tests can spend the whole `amount`.
+    const LOCKED_BALANCE: Balance = 10 * ONE_NEAR;
+
     fn members() -> Vec<MultisigMember> {
         vec![
             MultisigMember::Account {
@@ -554,10 +1422,20 @@ mod tests {
             .predecessor_account_id(account_id.clone())
             .signer_account_id(account_id)
             .signer_account_pk(key)
-            .account_balance(amount)
+            .account_balance(amount + LOCKED_BALANCE)
             .build()
     }
 
+    fn testing_env_with_promise_result(context: VMContext, result: PromiseResult) {
+        testing_env!(
+            context,
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result]
+        );
+    }
+
     fn context_with_key_future(key: PublicKey, amount: Balance) -> VMContext {
         VMContextBuilder::new()
             .current_account_id(alice())
@@ -565,7 +1443,7 @@ mod tests {
             .predecessor_account_id(alice())
             .signer_account_id(alice())
             .signer_account_pk(key)
-            .account_balance(amount)
+            .account_balance(amount + LOCKED_BALANCE)
             .build()
     }
 
@@ -578,18 +1456,19 @@ mod tests {
                 .unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
+        let mut c = Contract::new(members(), 3).unwrap();
         let request = MultiSigRequest {
             receiver_id: bob(),
             actions: vec![MultiSigRequestAction::Transfer {
                 amount: amount.into(),
+                metadata: payment_metadata(),
             }],
         };
-        let request_id = c.add_request(request.clone()).request_id;
+        let request_id = c.add_request(request.clone()).unwrap().request_id;
 
-        assert_eq!(c.get_request(request_id), request);
+        assert_eq!(c.get_request(request_id).unwrap(), request);
         assert_eq!(c.list_request_ids(), vec![request_id]);
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         assert_eq!(c.requests.len(), 1);
         assert_eq!(c.confirmations.get(&request_id).unwrap().len(), 1);
         testing_env!(context_with_key(
@@ -598,11 +1477,11 @@ mod tests {
                 .unwrap(),
             amount
         ));
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         assert_eq!(c.confirmations.get(&request_id).unwrap().len(), 2);
-        assert_eq!(c.get_confirmations(request_id).len(), 2);
+        assert_eq!(c.get_confirmations(request_id).unwrap().len(), 2);
         testing_env!(context_with_account(bob(), amount));
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         // TODO: confirm that funds were transferred out via promise.
         assert_eq!(c.requests.len(), 0);
     }
@@ -616,23 +1495,24 @@ mod tests {
                 .unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
+        let mut c = Contract::new(members(), 3).unwrap();
         let request = MultiSigRequest {
             receiver_id: bob(),
             actions: vec![MultiSigRequestAction::Transfer {
                 amount: amount.into(),
+                metadata: payment_metadata(),
             }],
         };
-        let ret = c.add_request_and_confirm(request.clone());
+        let ret = c.add_request_and_confirm(request.clone()).unwrap();
         let request_id = if let PromiseOrValue::Value(v) = ret {
             v.request_id
         } else {
             panic!("Expected value");
         };
 
-        assert_eq!(c.get_request(request_id), request);
+        assert_eq!(c.get_request(request_id).unwrap(), request);
         assert_eq!(c.list_request_ids(), vec![request_id]);
-        // c.confirm(request_id);
+        // c.confirm(request_id).unwrap();
         assert_eq!(c.requests.len(), 1);
         assert_eq!(c.confirmations.get(&request_id).unwrap().len(), 1);
         testing_env!(context_with_key(
@@ -641,11 +1521,11 @@ mod tests {
                 .unwrap(),
             amount
         ));
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         assert_eq!(c.confirmations.get(&request_id).unwrap().len(), 2);
-        assert_eq!(c.get_confirmations(request_id).len(), 2);
+        assert_eq!(c.get_confirmations(request_id).unwrap().len(), 2);
         testing_env!(context_with_account(bob(), amount));
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         // TODO: confirm that funds were transferred out via promise.
         assert_eq!(c.requests.len(), 0);
     }
@@ -659,7 +1539,7 @@ mod tests {
                 .unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 1);
+        let mut c = Contract::new(members(), 1).unwrap();
         let new_key: PublicKey = "HghiythFFPjVXwc9BLNi8uqFmfQc1DWFrJQ4nE6ANo7R"
             .parse()
             .unwrap();
@@ -672,7 +1552,7 @@ mod tests {
             }],
         };
         // make request
-        c.add_request_and_confirm(request);
+        c.add_request_and_confirm(request).unwrap();
         // should be empty now
         assert_eq!(c.requests.len(), 0);
         // switch accounts
@@ -686,10 +1566,11 @@ mod tests {
             receiver_id: alice(),
             actions: vec![MultiSigRequestAction::Transfer {
                 amount: amount.into(),
+                metadata: payment_metadata(),
             }],
         };
         // make request but don't confirm
-        c.add_request(request2);
+        c.add_request(request2).unwrap();
         // should have 1 request now
         let new_member = MultisigMember::AccessKey {
             public_key: new_key,
@@ -704,23 +1585,25 @@ mod tests {
             }],
         };
         // make request and confirm
-        c.add_request_and_confirm(request3);
+        c.add_request_and_confirm(request3).unwrap();
         // should be empty now
         assert_eq!(c.requests.len(), 0);
         assert_eq!(c.get_num_requests_per_member(new_member), 0);
     }
 
     #[test]
-    #[should_panic]
     fn test_panics_add_key_different_account() {
         let amount = 1_000;
         testing_env!(context_with_key(
-            PublicKey::try_from(Vec::from("Eg2jtsiMrprn7zgKKUk79qM1hWhANsFyE6JSX4txLEuy")).unwrap(),
+            "ed25519:Eg2jtsiMrprn7zgKKUk79qM1hWhANsFyE6JSX4txLEuy"
+                .parse()
+                .unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 1);
-        let new_key: PublicKey =
-            PublicKey::try_from(Vec::from("HghiythFFPjVXwc9BLNi8uqFmfQc1DWFrJQ4nE6ANo7R")).unwrap();
+        let mut c = Contract::new(members(), 1).unwrap();
+        let new_key: PublicKey = "ed25519:HghiythFFPjVXwc9BLNi8uqFmfQc1DWFrJQ4nE6ANo7R"
+            .parse()
+            .unwrap();
         // vm current_account_id is alice, receiver_id must be alice
         let request = MultiSigRequest {
             receiver_id: bob(),
@@ -730,7 +1613,10 @@ mod tests {
             }],
         };
         // make request
-        c.add_request_and_confirm(request);
+        assert!(matches!(
+            c.add_request_and_confirm(request),
+            Err(ContractError::NotSelfRequest)
+        ));
     }
 
     #[test]
@@ -740,7 +1626,7 @@ mod tests {
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 1);
+        let mut c = Contract::new(members(), 1).unwrap();
         let request_id = c
             .add_request(MultiSigRequest {
                 receiver_id: alice(),
@@ -748,55 +1634,1281 @@ mod tests {
                     num_confirmations: 2,
                 }],
             })
+            .unwrap()
             .request_id;
 
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         assert_eq!(c.num_confirmations, 2);
     }
 
     #[test]
-    #[should_panic]
+    fn test_spendable_balance() {
+        let amount = 1_000;
+        testing_env!(context_with_account(bob(), amount));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        assert_eq!(
+            c.get_spendable_balance().0,
+            amount + LOCKED_BALANCE - storage_cost - DEFAULT_STORAGE_RESERVE
+        );
+
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetStorageReserve {
+                storage_reserve: ONE_NEAR.into(),
+            }],
+        })
+        .unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::NearEscrowTransfer {
+                receiver_id: bob(),
+                amount: amount.into(),
+                metadata: payment_metadata(),
+                is_cancellable: true,
+            }],
+        })
+        .unwrap();
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        assert_eq!(c.get_config().storage_reserve, U128(ONE_NEAR));
+        assert_eq!(
+            c.get_spendable_balance().0,
+            LOCKED_BALANCE - storage_cost - ONE_NEAR
+        );
+
+        // payments are checked together, each of these fits on its own
+        let half = c.get_spendable_balance().0 / 2 + 1;
+        let transfer = MultiSigRequestAction::Transfer {
+            amount: half.into(),
+            metadata: payment_metadata(),
+        };
+        assert!(matches!(
+            c.add_request_and_confirm(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![transfer.clone(), transfer],
+            }),
+            Err(ContractError::InsufficientBalance { token_id: None, requested, .. })
+                if requested == 2 * half
+        ));
+    }
+
+    #[test]
+    fn test_get_config() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetRequestTtl {
+                request_ttl: Some(U64(10)),
+            }],
+        })
+        .unwrap();
+
+        let config = c.get_config();
+        assert_eq!(config.num_confirmations, 1);
+        assert_eq!(config.active_requests_limit, ACTIVE_REQUESTS_LIMIT);
+        assert_eq!(config.request_cooldown, U64(REQUEST_COOLDOWN));
+        assert_eq!(config.num_members, 4);
+        assert_eq!(config.request_ttl, Some(U64(10)));
+        assert_eq!(config.timelock_policy, None);
+        assert_eq!(config.state_version, STATE_VERSION);
+        assert_eq!(
+            c.contract_source_metadata().version,
+            Some(env!("CARGO_PKG_VERSION").to_string())
+        );
+    }
+
+    #[test]
+    fn test_action_threshold() {
+        let amount = 1_000;
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetActionThreshold {
+                action: ActionKind::Transfer,
+                num_confirmations: Some(2),
+            }],
+        })
+        .unwrap();
+        assert_eq!(c.get_action_thresholds(), vec![(ActionKind::Transfer, 2)]);
+
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![
+                    MultiSigRequestAction::CreateAccount,
+                    MultiSigRequestAction::Transfer {
+                        amount: amount.into(),
+                        metadata: payment_metadata(),
+                    },
+                ],
+            })
+            .unwrap()
+            .request_id;
+        assert_eq!(c.get_request_threshold(request_id).unwrap(), 2);
+        c.confirm(request_id).unwrap();
+        assert_eq!(c.requests.len(), 1);
+        testing_env!(context_with_account(bob(), amount));
+        c.confirm(request_id).unwrap();
+        assert_eq!(c.requests.len(), 0);
+    }
+
+    #[test]
+    fn test_amount_tiers() {
+        let amount = 1_000;
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetAmountTiers {
+                token_id: None,
+                tiers: vec![
+                    AmountTier {
+                        min_amount: 100.into(),
+                        required: RequiredConfirmations::Count(2),
+                    },
+                    AmountTier {
+                        min_amount: 900.into(),
+                        required: RequiredConfirmations::AllMembers,
+                    },
+                ],
+            }],
+        })
+        .unwrap();
+
+        let transfer = |amount: u128| MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: amount.into(),
+                metadata: payment_metadata(),
+            }],
+        };
+        let small = c.add_request(transfer(50)).unwrap().request_id;
+        let medium = c.add_request(transfer(500)).unwrap().request_id;
+        let large = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![
+                    MultiSigRequestAction::Transfer {
+                        amount: 450.into(),
+                        metadata: payment_metadata(),
+                    },
+                    MultiSigRequestAction::Transfer {
+                        amount: 450.into(),
+                        metadata: payment_metadata(),
+                    },
+                ],
+            })
+            .unwrap()
+            .request_id;
+        assert_eq!(c.get_request_threshold(small).unwrap(), 1);
+        assert_eq!(c.get_request_threshold(medium).unwrap(), 2);
+        assert_eq!(c.get_request_threshold(large).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_budgets() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let set_budget = |category: &str, budget: Option<Budget>| MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetBudget {
+                category: category.to_string(),
+                token_id: None,
+                budget,
+            }],
+        };
+        c.add_request_and_confirm(set_budget(
+            "operations",
+            Some(Budget {
+                amount: 100.into(),
+                period: BudgetPeriod::Quarter,
+            }),
+        ))
+        .unwrap();
+        assert!(matches!(
+            c.add_request_and_confirm(set_budget("", None)),
+            Err(ContractError::MissingCategory)
+        ));
+
+        // escrows can't be bundled, the request would stop at the first escrow
+        let escrow = MultiSigRequestAction::NearEscrowTransfer {
+            receiver_id: bob(),
+            amount: 10.into(),
+            metadata: payment_metadata(),
+            is_cancellable: true,
+        };
+        assert!(matches!(
+            c.add_request(MultiSigRequest {
+                receiver_id: alice(),
+                actions: vec![escrow.clone(), escrow],
+            }),
+            Err(ContractError::NotSingleAction)
+        ));
+
+        let transfer = |amount: u128, category: &str| MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: amount.into(),
+                metadata: PaymentMetadata {
+                    category: Some(category.to_string()),
+                    ..Default::default()
+                },
+            }],
+        };
+        c.add_request_and_confirm(transfer(60, "operations"))
+            .unwrap();
+        // categories without budget are not limited
+        c.add_request_and_confirm(transfer(500, "marketing"))
+            .unwrap();
+        let status = c.get_budget("operations".to_string(), None).unwrap();
+        assert_eq!(status.spent, U128(60));
+        assert_eq!(status.remaining, U128(40));
+        assert_eq!(status.period_end, U64(90 * 86_400_000_000_000));
+        assert!(matches!(
+            c.add_request_and_confirm(transfer(50, "operations")),
+            Err(ContractError::BudgetExceeded {
+                remaining: 40,
+                requested: 50,
+                ..
+            })
+        ));
+
+        // the budget is spent again from the next quarter
+        let mut context = context_with_account(bob(), 1_000);
+        context.block_timestamp = status.period_end.0;
+        testing_env!(context);
+        c.add_request_and_confirm(transfer(50, "operations"))
+            .unwrap();
+        assert_eq!(c.get_budgets()[0].spent, U128(50));
+
+        // changing the period mid-period keeps what was spent
+        c.add_request_and_confirm(set_budget(
+            "operations",
+            Some(Budget {
+                amount: 100.into(),
+                period: BudgetPeriod::Month,
+            }),
+        ))
+        .unwrap();
+        let status = c.get_budget("operations".to_string(), None).unwrap();
+        assert_eq!(status.spent, U128(50));
+        assert_eq!(status.remaining, U128(50));
+
+        c.add_request_and_confirm(set_budget("operations", None))
+            .unwrap();
+        assert!(c.get_budgets().is_empty());
+        c.add_request_and_confirm(transfer(200, "operations"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_budget_refunds() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetBudget {
+                category: "operations".to_string(),
+                token_id: None,
+                budget: Some(Budget {
+                    amount: 500.into(),
+                    period: BudgetPeriod::Month,
+                }),
+            }],
+        })
+        .unwrap();
+        let spent = |c: &Contract| c.get_budget("operations".to_string(), None).unwrap().spent;
+
+        // a failed transfer gives its amount back
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: 100.into(),
+                metadata: payment_metadata(),
+            }],
+        })
+        .unwrap();
+        assert_eq!(spent(&c), U128(100));
+        testing_env_with_promise_result(context_with_account(bob(), 1_000), PromiseResult::Failed);
+        c.callback_request_executed(1, 1).unwrap();
+        assert_eq!(spent(&c), U128(0));
+    }
+
+    #[test]
+    fn test_budget_periods() {
+        // 2024-03-15T16:30:00Z
+        let timestamp = 1_710_520_200_000_000_000;
+        let secs = |seconds: u64| seconds * 1_000_000_000;
+        assert_eq!(
+            budgets::period_bounds(BudgetPeriod::Month, timestamp),
+            (secs(1_709_251_200), secs(1_711_929_600))
+        );
+        assert_eq!(
+            budgets::period_bounds(BudgetPeriod::Quarter, timestamp),
+            (secs(1_704_067_200), secs(1_711_929_600))
+        );
+        assert_eq!(
+            budgets::period_bounds(BudgetPeriod::Year, timestamp),
+            (secs(1_704_067_200), secs(1_735_689_600))
+        );
+        // 2023-12-10, the period ends the next year
+        assert_eq!(
+            budgets::period_bounds(BudgetPeriod::Quarter, secs(1_702_166_400)),
+            (secs(1_696_118_400), secs(1_704_067_200))
+        );
+    }
+
+    #[test]
+    fn test_delete_expired_request() {
+        let amount = 1_000;
+        testing_env!(context_with_account(bob(), amount));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetRequestTtl {
+                request_ttl: Some(U64(REQUEST_COOLDOWN)),
+            }],
+        })
+        .unwrap();
+
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        let member = MultisigMember::AccessKey {
+            public_key: PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+        };
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![MultiSigRequestAction::Transfer {
+                    amount: amount.into(),
+                    metadata: payment_metadata(),
+                }],
+            })
+            .unwrap()
+            .request_id;
+        assert_eq!(c.list_request_ids(), vec![request_id]);
+        assert!(c.list_expired_request_ids().is_empty());
+
+        let mut context =
+            context_with_account(AccountId::new_unchecked("carol".to_string()), amount);
+        context.block_timestamp = REQUEST_COOLDOWN + 1;
+        testing_env!(context);
+        assert!(c.list_request_ids().is_empty());
+        assert_eq!(c.list_expired_request_ids(), vec![request_id]);
+        c.delete_expired_request(request_id).unwrap();
+        assert_eq!(c.requests.len(), 0);
+        assert_eq!(c.get_num_requests_per_member(member), 0);
+    }
+
+    #[test]
+    fn test_timelock() {
+        let amount = 1_000;
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::SetTimelockPolicy {
+                policy: Some(TimelockPolicy {
+                    duration: 100.into(),
+                    transfer_threshold: 500.into(),
+                }),
+            }],
+        })
+        .unwrap();
+        // keys of the multisig and token payments are timelocked whatever the amount
+        let add_key = MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::AddKey {
+                public_key: PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+                permission: None,
+            }],
+        };
+        assert_eq!(c.timelock_duration(&add_key), Some(100));
+        let ft_escrow = MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::FTEscrowTransfer {
+                receiver_id: bob(),
+                amount: 1.into(),
+                token_id: AccountId::new_unchecked("usdc".to_string()),
+                metadata: payment_metadata(),
+                is_cancellable: true,
+            }],
+        };
+        assert_eq!(c.timelock_duration(&ft_escrow), Some(100));
+        let small = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![MultiSigRequestAction::Transfer {
+                    amount: 100.into(),
+                    metadata: payment_metadata(),
+                }],
+            })
+            .unwrap()
+            .request_id;
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: alice(),
+                actions: vec![MultiSigRequestAction::AddMember {
+                    member: MultisigMember::Account {
+                        account_id: AccountId::new_unchecked("carol".to_string()),
+                    },
+                }],
+            })
+            .unwrap()
+            .request_id;
+        c.confirm(small).unwrap();
+        assert_eq!(c.requests.len(), 1);
+        match c.confirm(request_id).unwrap() {
+            PromiseOrValue::Value(MultiSigResponse {
+                response: FuncResponse::Queued(executable_at),
+                ..
+            }) => assert_eq!(executable_at.0, 100),
+            _ => panic!("Expected request to be queued"),
+        };
+        assert_eq!(c.list_queued_requests(), vec![(request_id, 100.into())]);
+        assert_eq!(c.members.len(), 4);
+
+        let mut context = context_with_account(bob(), amount);
+        context.block_timestamp = 100;
+        testing_env!(context);
+        c.execute(request_id).unwrap();
+        assert_eq!(c.requests.len(), 0);
+        assert_eq!(c.members.len(), 5);
+
+        // lifting the timelock is itself timelocked
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: alice(),
+                actions: vec![MultiSigRequestAction::SetTimelockPolicy { policy: None }],
+            })
+            .unwrap()
+            .request_id;
+        c.confirm(request_id).unwrap();
+        assert_eq!(c.list_queued_requests(), vec![(request_id, 200.into())]);
+        assert!(c.get_timelock_policy().is_some());
+    }
+
+    #[test]
+    fn test_revoke_confirmation() {
+        let amount = 1_000;
+        let mut context = context_with_account(bob(), amount);
+        context.block_timestamp = 42;
+        testing_env!(context);
+        let mut c = Contract::new(members(), 3).unwrap();
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![MultiSigRequestAction::Transfer {
+                    amount: amount.into(),
+                    metadata: payment_metadata(),
+                }],
+            })
+            .unwrap()
+            .request_id;
+        c.confirm(request_id).unwrap();
+        assert_eq!(
+            c.get_confirmations_with_timestamps(request_id).unwrap(),
+            vec![Confirmation {
+                member: MultisigMember::Account { account_id: bob() },
+                timestamp: 42.into(),
+            }]
+        );
+        c.revoke_confirmation(request_id).unwrap();
+        assert!(c.get_confirmations(request_id).unwrap().is_empty());
+        assert_eq!(c.requests.len(), 1);
+    }
+
+    #[test]
+    fn test_reject_closes_request() {
+        let amount = 1_000;
+        testing_env!(context_with_account(bob(), amount));
+        let mut c = Contract::new(members(), 3).unwrap();
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![MultiSigRequestAction::Transfer {
+                    amount: amount.into(),
+                    metadata: payment_metadata(),
+                }],
+            })
+            .unwrap()
+            .request_id;
+        c.reject(request_id).unwrap();
+        assert_eq!(c.get_rejections(request_id).unwrap().len(), 1);
+        assert_eq!(c.requests.len(), 1);
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        c.reject(request_id).unwrap();
+        assert_eq!(c.requests.len(), 0);
+        assert!(c.rejections.get(&request_id).is_none());
+        assert_eq!(
+            c.get_num_requests_per_member(MultisigMember::Account { account_id: bob() }),
+            0
+        );
+    }
+
+    #[test]
+    fn test_reject_after_deleting_rejecting_members() {
+        let amount = 1_000;
+        let key: PublicKey = "ed25519:Eg2jtsiMrprn7zgKKUk79qM1hWhANsFyE6JSX4txLEuy"
+            .parse()
+            .unwrap();
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        let mut c = Contract::new(members(), 2).unwrap();
+        let request_id = c
+            .add_request(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![MultiSigRequestAction::Transfer {
+                    amount: amount.into(),
+                    metadata: payment_metadata(),
+                }],
+            })
+            .unwrap()
+            .request_id;
+        testing_env!(context_with_account(bob(), amount));
+        c.reject(request_id).unwrap();
+        testing_env!(context_with_key(key.clone(), amount));
+        c.reject(request_id).unwrap();
+        assert_eq!(c.requests.len(), 1);
+
+        for member in [
+            MultisigMember::Account { account_id: bob() },
+            MultisigMember::AccessKey {
+                public_key: key.clone(),
+            },
+        ] {
+            testing_env!(context_with_key(
+                PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+                amount
+            ));
+            let delete_id = c
+                .add_request(MultiSigRequest {
+                    receiver_id: alice(),
+                    actions: vec![MultiSigRequestAction::DeleteMember { member }],
+                })
+                .unwrap()
+                .request_id;
+            c.confirm(delete_id).unwrap();
+            testing_env!(context_with_key(key.clone(), amount));
+            c.confirm(delete_id).unwrap();
+        }
+        assert_eq!(c.members.len(), 2);
+
+        // only the rejection of a current member counts
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        c.reject(request_id).unwrap();
+        assert_eq!(c.requests.len(), 0);
+    }
+
+    #[test]
+    fn test_staking_balances() {
+        testing_env!(context_with_account(alice(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let pool = AccountId::new_unchecked("pool".to_string());
+        let success = || PromiseResult::Successful(vec![]);
+
+        testing_env_with_promise_result(context_with_account(alice(), 1_000), success());
+        c.callback_staking_action(0, pool.clone(), ActionKind::DepositAndStake, 500.into())
+            .unwrap();
+        c.callback_staking_action(1, pool.clone(), ActionKind::Unstake, 200.into())
+            .unwrap();
+        c.callback_staking_action(2, pool.clone(), ActionKind::Withdraw, 50.into())
+            .unwrap();
+
+        let balance = c.get_treasury_balance();
+        assert_eq!(balance.liquid.0, LOCKED_BALANCE + 1_000);
+        assert_eq!(balance.staked.0, 300);
+        assert_eq!(balance.unstaked.0, 150);
+        assert_eq!(balance.total.0, LOCKED_BALANCE + 1_450);
+
+        // the unstaked balance is read from the pool, rewards included
+        c.callback_staking_action(3, pool.clone(), ActionKind::UnstakeAll, 0.into())
+            .unwrap();
+        c.callback_unstaked_balance(3, pool.clone(), Ok(460.into()));
+        let balance = c.get_treasury_balance();
+        assert_eq!(balance.staked.0, 0);
+        assert_eq!(balance.unstaked.0, 460);
+
+        c.callback_staking_action(4, pool.clone(), ActionKind::Withdraw, 460.into())
+            .unwrap();
+        assert!(c.get_treasury_balance().staking_pools.is_empty());
+
+        testing_env_with_promise_result(
+            context_with_account(alice(), 1_000),
+            PromiseResult::Failed,
+        );
+        assert_eq!(
+            c.callback_staking_action(5, pool.clone(), ActionKind::Unstake, 10.into())
+                .err(),
+            Some(ContractError::StakingPoolFailed {
+                staking_pool_id: pool,
+                action: ActionKind::Unstake,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ft_on_transfer() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let token = AccountId::new_unchecked("token".to_string());
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::RegisterToken {
+                token_id: token.clone(),
+            }],
+        })
+        .unwrap();
+        let metadata = TokenMetadata {
+            symbol: "TKN".to_string(),
+            decimals: 18,
+        };
+        c.callback_token_metadata(0, token.clone(), Ok(metadata))
+            .unwrap();
+
+        testing_env!(context_with_account(token.clone(), 1_000));
+        c.ft_on_transfer(bob(), 10.into(), "invoice 1".to_string())
+            .unwrap();
+        c.ft_on_transfer(alice(), 20.into(), "".to_string())
+            .unwrap();
+        c.ft_on_transfer(bob(), 5.into(), "invoice 2".to_string())
+            .unwrap();
+
+        // each payment is kept with its memo
+        let payments = c.get_incoming_payments(Some(token.clone()), Some(bob()), None, None);
+        assert_eq!(
+            payments
+                .iter()
+                .map(|p| (p.amount.0, p.memo.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (10, Some("invoice 1".to_string())),
+                (5, Some("invoice 2".to_string()))
+            ]
+        );
+        let payments = c.get_incoming_payments(Some(token.clone()), Some(bob()), Some(1), Some(1));
+        assert_eq!(payments[0].amount.0, 5);
+        assert_eq!(c.get_incoming_payments_length(), 3);
+        assert_eq!(
+            c.get_incoming_payments(Some(token.clone()), None, None, None)
+                .len(),
+            3
+        );
+        let payments = c.get_incoming_payments(None, None, Some(1), Some(1));
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].sender_id, alice());
+        assert_eq!(c.incoming_totals.get(&token), Some(35));
+    }
+
+    #[test]
+    fn test_token_registry() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let token = AccountId::new_unchecked("usdc".to_string());
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::RegisterToken {
+                token_id: token.clone(),
+            }],
+        })
+        .unwrap();
+        // the token is registered once its metadata is returned
+        assert!(c.get_registered_tokens().is_empty());
+        assert_eq!(
+            c.callback_token_metadata(0, token.clone(), Err(near_sdk::PromiseError::Failed))
+                .err(),
+            Some(ContractError::TokenMetadataUnavailable(token.clone()))
+        );
+        assert!(c.get_registered_tokens().is_empty());
+
+        let metadata = TokenMetadata {
+            symbol: "USDC".to_string(),
+            decimals: 6,
+        };
+        c.callback_token_metadata(0, token.clone(), Ok(metadata.clone()))
+            .unwrap();
+        assert_eq!(
+            c.get_registered_tokens(),
+            vec![(token.clone(), Some(metadata))]
+        );
+
+        testing_env!(context_with_account(token.clone(), 1_000));
+        c.ft_on_transfer(bob(), 1_500_000.into(), "".to_string())
+            .unwrap();
+        assert_eq!(
+            c.get_token_balances(),
+            vec![TokenBalance {
+                token_id: token,
+                symbol: Some("USDC".to_string()),
+                committed: "0".to_string(),
+                received: "1.5".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_treasury_overview() {
+        let amount = 1_000;
+        testing_env!(context_with_account(bob(), amount));
+        let mut c = Contract::new(members(), 2).unwrap();
+        let token = AccountId::new_unchecked("usdc".to_string());
+        c.tokens.insert(&token, &None);
+
+        c.add_request(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: 100.into(),
+                metadata: payment_metadata(),
+            }],
+        })
+        .unwrap();
+        c.add_request(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::FTEscrowTransfer {
+                receiver_id: alice(),
+                amount: 30.into(),
+                token_id: token.clone(),
+                metadata: payment_metadata(),
+                is_cancellable: true,
+            }],
+        })
+        .unwrap();
+        c.callback_token_balance(token.clone(), Ok(U128(50)))
+            .unwrap();
+        c.ft_committed_balance.insert(&token, &40);
+
+        let overview = c.get_treasury_overview();
+        assert_eq!(overview.near.total.0, amount + LOCKED_BALANCE);
+        assert_eq!(overview.near.pending.0, 100);
+        assert_eq!(overview.near.available.0, c.get_spendable_balance().0 - 100);
+        assert_eq!(
+            overview.tokens,
+            vec![AssetOverview {
+                token_id: Some(token),
+                total: U128(50),
+                updated_at: Some(U64(0)),
+                committed: U128(40),
+                pending: U128(30),
+                available: U128(0),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_token_shortfalls() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 2).unwrap();
+        let token = AccountId::new_unchecked("usdc".to_string());
+        c.tokens.insert(&token, &None);
+        c.ft_committed_balance.insert(&token, &40);
+        let logs = get_logs().len();
+
+        c.callback_token_balance(token.clone(), Ok(U128(50)))
+            .unwrap();
+        assert!(c.get_token_shortfalls().is_empty());
+        assert_eq!(get_logs().len(), logs);
+
+        c.callback_token_balance(token.clone(), Ok(U128(25)))
+            .unwrap();
+        assert_eq!(
+            c.get_token_shortfalls(),
+            vec![TokenShortfall {
+                token_id: token.clone(),
+                committed: U128(40),
+                balance: U128(25),
+                shortfall: U128(15),
+                checked_at: U64(0),
+            }]
+        );
+        let event = EventLog::from_log(&get_logs()[logs])
+            .expect("not an event")
+            .event;
+        assert_eq!(
+            event,
+            PaymentsEvent::CommittedBalanceShortfall {
+                token_id: token,
+                committed: U128(40),
+                balance: U128(25),
+            }
+        );
+
+        // tokens committed without being registered are checked too
+        let other = AccountId::new_unchecked("usdt".to_string());
+        c.ft_committed_balance.insert(&other, &10);
+        c.callback_token_balance(other.clone(), Ok(U128(5)))
+            .unwrap();
+        assert_eq!(c.get_token_shortfalls()[1].token_id, other);
+        assert_eq!(
+            c.callback_token_balance(other.clone(), Err(near_sdk::PromiseError::Failed)),
+            Err(ContractError::TokenBalanceUnavailable(other))
+        );
+
+        c.tokens.clear();
+        c.ft_committed_balance.clear();
+        assert_eq!(c.reconcile_tokens(), Err(ContractError::NoTokens));
+    }
+
+    #[test]
+    fn test_events() {
+        let amount = 1_000;
+        testing_env!(context_with_account(bob(), amount));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let member = MultisigMember::Account { account_id: bob() };
+        let request = MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::NearEscrowTransfer {
+                receiver_id: bob(),
+                amount: amount.into(),
+                metadata: payment_metadata(),
+                is_cancellable: true,
+            }],
+        };
+
+        testing_env!(context_with_account(bob(), amount));
+        c.add_request_and_confirm(request).unwrap();
+        let events: Vec<PaymentsEvent> = get_logs()
+            .iter()
+            .map(|log| EventLog::from_log(log).expect("not an event").event)
+            .collect();
+        assert_eq!(events.len(), 5);
+        assert_eq!(
+            events[..3],
+            [
+                PaymentsEvent::RequestAdded {
+                    request_id: 0,
+                    member: member.clone(),
+                    receiver_id: alice(),
+                    actions: vec![ActionKind::NearEscrowTransfer],
+                },
+                PaymentsEvent::RequestConfirmed {
+                    request_id: 0,
+                    member,
+                },
+                PaymentsEvent::CommittedBalanceChanged {
+                    token_id: None,
+                    committed: amount.into(),
+                },
+            ]
+        );
+        assert!(matches!(events[3], PaymentsEvent::EscrowCreated { .. }));
+        assert_eq!(
+            events[4],
+            PaymentsEvent::RequestExecuted {
+                request_id: 0,
+                success: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_execution_outcome() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let request = MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: 1.into(),
+                metadata: payment_metadata(),
+            }],
+        };
+        c.add_request_and_confirm(request.clone()).unwrap();
+        c.add_request_and_confirm(request).unwrap();
+
+        testing_env_with_promise_result(
+            context_with_account(alice(), 1_000),
+            PromiseResult::Successful(
+                serde_json::to_vec(&MultiSigResponse::new(
+                    0,
+                    FuncResponse::EscrowPayment([1; 32].into()),
+                ))
+                .unwrap(),
+            ),
+        );
+        let response = c.callback_request_executed(0, 0).unwrap();
+        assert_eq!(response.request_id, 0);
+        assert!(matches!(response.response, FuncResponse::EscrowPayment(_)));
+        assert!(c.get_execution_outcome(0).unwrap().success);
+        assert_eq!(
+            c.get_history(None, None, None, None, None, Some(1))[0].payment_ids,
+            vec![[1; 32].into()]
+        );
+
+        testing_env_with_promise_result(context_with_account(bob(), 1_000), PromiseResult::Failed);
+        c.callback_request_executed(1, 1).unwrap();
+        assert!(!c.get_execution_outcome(1).unwrap().success);
+        assert!(c.get_execution_outcome(2).is_none());
+    }
+
+    #[test]
+    fn test_history() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let transfer = MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: 1.into(),
+                metadata: payment_metadata(),
+            }],
+        };
+        let key = PublicKey::try_from(TEST_KEY.to_vec()).unwrap();
+        let key_member = MultisigMember::AccessKey {
+            public_key: key.clone(),
+        };
+        let bob_member = MultisigMember::Account { account_id: bob() };
+
+        // executed by the key member, its promise fails
+        testing_env!(context_with_key(key, 1_000));
+        c.add_request_and_confirm(transfer.clone()).unwrap();
+        testing_env_with_promise_result(
+            context_with_account(alice(), 1_000),
+            PromiseResult::Failed,
+        );
+        c.callback_request_executed(0, 0).unwrap();
+
+        // proposed by bob and deleted after the cooldown
+        testing_env!(context_with_account(bob(), 1_000));
+        c.add_request(transfer).unwrap();
+        let mut context = context_with_account(bob(), 1_000);
+        context.block_timestamp = REQUEST_COOLDOWN + 1;
+        testing_env!(context);
+        c.delete_request(1).unwrap();
+
+        assert_eq!(c.get_history_length(None, None, None), 2);
+        let history = c.get_history(None, None, None, None, None, None);
+        assert_eq!(history[0].proposer, key_member);
+        assert_eq!(history[0].confirmations[0].member, key_member);
+        assert_eq!(
+            history[0].status,
+            RequestStatus::Executed { success: false }
+        );
+        assert_eq!(
+            history[1].status,
+            RequestStatus::Deleted {
+                reason: DeletionReason::Deleted
+            }
+        );
+        assert_eq!(history[1].finished_at, U64(REQUEST_COOLDOWN + 1));
+
+        let by_bob = c.get_history(Some(bob_member), None, None, None, None, None);
+        assert_eq!(by_bob.len(), 1);
+        assert_eq!(by_bob[0].request_id, 1);
+        let recent = c.get_history(
+            None,
+            Some(ActionKind::Transfer),
+            Some(U64(1)),
+            None,
+            None,
+            None,
+        );
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].request_id, 1);
+        assert!(c
+            .get_history(None, Some(ActionKind::AddKey), None, None, None, None)
+            .is_empty());
+        assert_eq!(
+            c.get_history(None, None, None, None, Some(1), Some(5))
+                .len(),
+            1
+        );
+        assert_eq!(
+            c.get_history_length(Some(key_member), Some(U64(1)), None),
+            0
+        );
+        assert_eq!(
+            c.get_history_length(None, None, Some(U64(REQUEST_COOLDOWN))),
+            1
+        );
+        assert_eq!(history[1].request.actions[0].kind(), ActionKind::Transfer);
+        assert_eq!(history[1].payments()[0].amount, U128(1));
+
+        // the code of a deployment is not kept, the action filter applies before pagination
+        c.add_request(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::DeployContract {
+                code: vec![1, 2, 3].into(),
+            }],
+        })
+        .unwrap();
+        let mut context = context_with_account(bob(), 1_000);
+        context.block_timestamp = 2 * REQUEST_COOLDOWN + 2;
+        testing_env!(context);
+        c.delete_request(2).unwrap();
+        let deployments = c.get_history(
+            None,
+            Some(ActionKind::DeployContract),
+            None,
+            None,
+            Some(0),
+            Some(1),
+        );
+        assert_eq!(deployments.len(), 1);
+        assert_eq!(
+            deployments[0].request.actions,
+            vec![MultiSigRequestAction::DeployContract {
+                code: vec![].into()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        testing_env!(context_with_account(alice(), 1_000));
+        let member = MultisigMember::Account { account_id: bob() };
+        let mut members = UnorderedSet::new(StorageKeys::Members);
+        members.insert(&member);
+        let mut requests = UnorderedMap::new(StorageKeys::Requests);
+        requests.insert(
+            &0,
+            &MultiSigRequestWithSignerV1 {
+                receiver_id: alice(),
+                actions: vec![MultiSigRequestActionV1::SetNumConfirmations {
+                    num_confirmations: 1,
+                }],
+                member: member.clone(),
+                added_timestamp: 1,
+            },
+        );
+        requests.insert(
+            &1,
+            &MultiSigRequestWithSignerV1 {
+                receiver_id: alice(),
+                actions: vec![MultiSigRequestActionV1::NearEscrowTransfer {
+                    receiver_id: bob(),
+                    amount: 10.into(),
+                    label: "pending".to_string(),
+                    is_cancellable: true,
+                }],
+                member: member.clone(),
+                added_timestamp: 1,
+            },
+        );
+        let mut confirmations = LookupMap::new(StorageKeys::Confirmations);
+        confirmations.insert(&0, &HashSet::from([member.to_string()]));
+        let mut num_requests_pk = LookupMap::new(StorageKeys::NumRequestsPk);
+        num_requests_pk.insert(&member.to_string(), &1);
+        let mut escrow_transfers = UnorderedMap::new(StorageKeys::EscrowTransfers);
+        escrow_transfers.insert(
+            &[1; 32],
+            &EscrowTransferV1 {
+                id: [1; 32],
+                receiver_id: bob(),
+                amount: 10,
+                label: "executed".to_string(),
+                is_locked: false,
+                token_account: None,
+            },
+        );
+        env::state_write(&ContractV1 {
+            members,
+            num_confirmations: 1,
+            request_nonce: 2,
+            requests,
+            confirmations,
+            num_requests_pk,
+            active_requests_limit: ACTIVE_REQUESTS_LIMIT,
+            near_committed_balance: 10,
+            ft_committed_balance: UnorderedMap::new(StorageKeys::FtCommittedBalances),
+            escrow_transfers,
+        });
+
+        let c = Contract::migrate();
+        assert_eq!(c.get_members(), vec![member.clone()]);
+        assert!(matches!(
+            c.requests.get(&0),
+            Some(VersionedMultiSigRequestWithSigner::V1(_))
+        ));
+        assert_eq!(
+            c.get_request(0).unwrap().actions,
+            vec![MultiSigRequestAction::SetNumConfirmations {
+                num_confirmations: 1
+            }]
+        );
+        assert_eq!(
+            c.get_confirmations_with_timestamps(0).unwrap(),
+            vec![Confirmation {
+                member: member.clone(),
+                timestamp: U64(1),
+            }]
+        );
+        assert_eq!(c.get_num_requests_per_member(member), 1);
+        assert_eq!(c.storage_reserve, DEFAULT_STORAGE_RESERVE);
+        // labels of payments are kept as metadata
+        assert_eq!(
+            c.get_request(1).unwrap().actions[0].metadata(),
+            Some(&PaymentMetadata::from_label("pending".to_string()))
+        );
+        assert_eq!(
+            c.get_payments(None, None).unwrap()[0].1.metadata,
+            PaymentMetadata::from_label("executed".to_string())
+        );
+
+        // migrating the current layout keeps the state as is
+        env::state_write(&c);
+        let c = Contract::migrate();
+        assert_eq!(c.get_request_nonce(), 2);
+        assert_eq!(c.list_request_ids(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_payment_metadata_limits() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let transfer = |metadata| MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: 1.into(),
+                metadata,
+            }],
+        };
+        assert!(matches!(
+            c.add_request(transfer(PaymentMetadata {
+                category: Some("c".repeat(MAX_METADATA_FIELD_LEN as usize + 1)),
+                ..Default::default()
+            })),
+            Err(ContractError::MetadataTooLong { field, .. }) if field == "category"
+        ));
+        assert!(matches!(
+            c.add_request(transfer(PaymentMetadata {
+                tags: vec!["tag".to_string(); MAX_TAGS as usize + 1],
+                ..Default::default()
+            })),
+            Err(ContractError::TooManyTags { .. })
+        ));
+        assert!(matches!(
+            c.add_request(transfer(PaymentMetadata {
+                tags: vec!["t".repeat(MAX_TAG_LEN as usize + 1)],
+                ..Default::default()
+            })),
+            Err(ContractError::MetadataTooLong { field, .. }) if field == "tags"
+        ));
+        // rejected requests don't count towards the active requests limit
+        assert_eq!(
+            c.get_num_requests_per_member(MultisigMember::Account { account_id: bob() }),
+            0
+        );
+        assert!(matches!(
+            c.add_request(transfer(PaymentMetadata::default())),
+            Err(ContractError::MissingCategory)
+        ));
+        c.add_request(transfer(PaymentMetadata {
+            label: Some("l".repeat(MAX_METADATA_FIELD_LEN as usize)),
+            category: Some("operations".to_string()),
+            tags: vec!["tag".to_string(); MAX_TAGS as usize],
+            ..Default::default()
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_payments_by_category_and_tag() {
+        testing_env!(context_with_account(bob(), 1_000));
+        let mut c = Contract::new(members(), 1).unwrap();
+        let escrow = |category: &str, tags: &[&str]| MultiSigRequestAction::NearEscrowTransfer {
+            receiver_id: bob(),
+            amount: 10.into(),
+            metadata: PaymentMetadata {
+                category: Some(category.to_string()),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            },
+            is_cancellable: true,
+        };
+        for action in [
+            escrow("payroll", &["q1"]),
+            escrow("payroll", &["q2"]),
+            escrow("marketing", &["q1", "events"]),
+        ] {
+            c.add_request_and_confirm(MultiSigRequest {
+                receiver_id: alice(),
+                actions: vec![action],
+            })
+            .unwrap();
+        }
+
+        let count = |category: Option<&str>, tag: Option<&str>| {
+            c.get_payments(category.map(String::from), tag.map(String::from))
+                .unwrap()
+                .len()
+        };
+        assert_eq!(count(None, None), 3);
+        assert_eq!(count(Some("payroll"), None), 2);
+        assert_eq!(count(None, Some("q1")), 2);
+        assert_eq!(count(Some("payroll"), Some("q1")), 1);
+        assert_eq!(count(Some("legal"), None), 0);
+    }
+
+    #[test]
     fn test_panics_on_second_confirm() {
         let amount = 1_000;
         testing_env!(context_with_key(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
+        let mut c = Contract::new(members(), 3).unwrap();
         let request_id = c
             .add_request(MultiSigRequest {
                 receiver_id: bob(),
                 actions: vec![MultiSigRequestAction::Transfer {
                     amount: amount.into(),
+                    metadata: payment_metadata(),
                 }],
             })
+            .unwrap()
             .request_id;
 
         assert_eq!(c.requests.len(), 1);
         assert_eq!(c.confirmations.get(&request_id).unwrap().len(), 0);
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         assert_eq!(c.confirmations.get(&request_id).unwrap().len(), 1);
-        c.confirm(request_id);
+        assert!(matches!(
+            c.confirm(request_id),
+            Err(ContractError::AlreadyConfirmed)
+        ));
     }
 
     #[test]
-    #[should_panic]
     fn test_panics_delete_request() {
         let amount = 1_000;
         testing_env!(context_with_key(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
+        let mut c = Contract::new(members(), 3).unwrap();
         let request_id = c
             .add_request(MultiSigRequest {
                 receiver_id: bob(),
                 actions: vec![MultiSigRequestAction::Transfer {
                     amount: amount.into(),
+                    metadata: payment_metadata(),
                 }],
             })
+            .unwrap()
             .request_id;
-        c.delete_request(request_id);
+        assert_eq!(
+            c.delete_request(request_id),
+            Err(ContractError::RequestCooldown)
+        );
     }
 
     #[test]
@@ -806,76 +2918,167 @@ mod tests {
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
+        let mut c = Contract::new(members(), 3).unwrap();
         let request_id = c
             .add_request(MultiSigRequest {
                 receiver_id: bob(),
                 actions: vec![MultiSigRequestAction::Transfer {
                     amount: amount.into(),
+                    metadata: payment_metadata(),
                 }],
             })
+            .unwrap()
             .request_id;
 
-        c.confirm(request_id);
+        c.confirm(request_id).unwrap();
         testing_env!(context_with_key_future(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        c.delete_request(request_id);
+        c.delete_request(request_id).unwrap();
         assert_eq!(c.requests.len(), 0);
         assert!(c.confirmations.get(&request_id).is_none());
     }
 
     #[test]
-    #[should_panic]
     fn test_delete_request_panic_wrong_key() {
         let amount = 1_000;
         testing_env!(context_with_key(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
+        let mut c = Contract::new(members(), 3).unwrap();
         let request_id = c
             .add_request(MultiSigRequest {
                 receiver_id: bob(),
                 actions: vec![MultiSigRequestAction::Transfer {
                     amount: amount.into(),
+                    metadata: payment_metadata(),
                 }],
             })
+            .unwrap()
             .request_id;
         testing_env!(context_with_key(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        c.delete_request(request_id);
+        assert_eq!(
+            c.delete_request(request_id),
+            Err(ContractError::RequestCooldown)
+        );
     }
 
     #[test]
-    #[should_panic]
     fn test_too_many_requests() {
         let amount = 1_000;
         testing_env!(context_with_key(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             amount
         ));
-        let mut c = Contract::new(members(), 3);
-        for _i in 0..16 {
-            c.add_request(MultiSigRequest {
+        let mut c = Contract::new(members(), 3).unwrap();
+        let request = MultiSigRequest {
+            receiver_id: bob(),
+            actions: vec![MultiSigRequestAction::Transfer {
+                amount: amount.into(),
+                metadata: payment_metadata(),
+            }],
+        };
+        for _i in 0..ACTIVE_REQUESTS_LIMIT {
+            c.add_request(request.clone()).unwrap();
+        }
+        assert!(matches!(
+            c.add_request(request),
+            Err(ContractError::TooManyActiveRequests {
+                limit: ACTIVE_REQUESTS_LIMIT
+            })
+        ));
+    }
+
+    #[test]
+    fn test_panics_delete_account_with_escrow() {
+        let amount = 1_000;
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            amount
+        ));
+        let mut c = Contract::new(members(), 1).unwrap();
+        c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::NearEscrowTransfer {
                 receiver_id: bob(),
-                actions: vec![MultiSigRequestAction::Transfer {
-                    amount: amount.into(),
-                }],
+                amount: amount.into(),
+                metadata: payment_metadata(),
+                is_cancellable: true,
+            }],
+        })
+        .unwrap();
+        let res = c.add_request_and_confirm(MultiSigRequest {
+            receiver_id: alice(),
+            actions: vec![MultiSigRequestAction::DeleteAccount {
+                beneficiary_id: bob(),
+            }],
+        });
+        assert!(matches!(res, Err(ContractError::PendingEscrowTransfers)));
+    }
+
+    #[test]
+    fn test_delete_key_and_account_of_other_account() {
+        testing_env!(context_with_key(
+            PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
+            1_000
+        ));
+        let mut c = Contract::new(members(), 1).unwrap();
+        for action in [
+            MultiSigRequestAction::DeleteKey {
+                public_key: "ed25519:HghiythFFPjVXwc9BLNi8uqFmfQc1DWFrJQ4nE6ANo7R"
+                    .parse()
+                    .unwrap(),
+            },
+            MultiSigRequestAction::DeleteAccount {
+                beneficiary_id: alice(),
+            },
+        ] {
+            let res = c.add_request_and_confirm(MultiSigRequest {
+                receiver_id: bob(),
+                actions: vec![action],
             });
+            assert!(matches!(res, Err(ContractError::NotSelfRequest)));
         }
     }
 
     #[test]
-    #[should_panic]
+    fn test_contract_error_json() {
+        assert_eq!(
+            serde_json::to_string(&ContractError::RequestNotFound(3)).unwrap(),
+            r#"{"RequestNotFound":3}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ContractError::AlreadyConfirmed).unwrap(),
+            r#""AlreadyConfirmed""#
+        );
+        assert_eq!(
+            serde_json::to_string(&ContractError::InsufficientBalance {
+                token_id: None,
+                available: 1,
+                requested: 2,
+            })
+            .unwrap(),
+            r#"{"InsufficientBalance":{"token_id":null,"available":1,"requested":2}}"#
+        );
+    }
+
+    #[test]
     fn test_too_many_confirmations() {
         testing_env!(context_with_key(
             PublicKey::try_from(TEST_KEY.to_vec()).unwrap(),
             1_000
         ));
-        let _ = Contract::new(members(), 5);
+        assert!(matches!(
+            Contract::new(members(), 5),
+            Err(ContractError::InvalidNumConfirmations {
+                num_confirmations: 5,
+                num_members: 4
+            })
+        ));
     }
 }
@@ -0,0 +1,271 @@
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::{near_bindgen, PublicKey};
+
+use crate::*;
+
+/// Version of the current state layout.
+pub const STATE_VERSION: u32 = 2;
+
+/// Storage key of the state version, the first release doesn't store it.
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Action layout of the first release, kept to decode its pending requests.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum MultiSigRequestActionV1 {
+    CreateAccount,
+    DeployContract {
+        code: Base64VecU8,
+    },
+    AddMember {
+        member: MultisigMember,
+    },
+    DeleteMember {
+        member: MultisigMember,
+    },
+    AddKey {
+        public_key: PublicKey,
+        permission: Option<FunctionCallPermission>,
+    },
+    SetNumConfirmations {
+        num_confirmations: u32,
+    },
+    SetActiveRequestsLimit {
+        active_requests_limit: u32,
+    },
+    Transfer {
+        amount: U128,
+    },
+    NearEscrowTransfer {
+        receiver_id: AccountId,
+        amount: U128,
+        label: String,
+        is_cancellable: bool,
+    },
+    FTEscrowTransfer {
+        receiver_id: AccountId,
+        amount: U128,
+        token_id: AccountId,
+        label: String,
+        is_cancellable: bool,
+    },
+}
+
+impl From<MultiSigRequestActionV1> for MultiSigRequestAction {
+    fn from(action: MultiSigRequestActionV1) -> Self {
+        match action {
+            MultiSigRequestActionV1::CreateAccount => Self::CreateAccount,
+            MultiSigRequestActionV1::DeployContract { code } => Self::DeployContract { code },
+            MultiSigRequestActionV1::AddMember { member } => Self::AddMember { member },
+            MultiSigRequestActionV1::DeleteMember { member } => Self::DeleteMember { member },
+            MultiSigRequestActionV1::AddKey {
+                public_key,
+                permission,
+            } => Self::AddKey {
+                public_key,
+                permission,
+            },
+            MultiSigRequestActionV1::SetNumConfirmations { num_confirmations } => {
+                Self::SetNumConfirmations { num_confirmations }
+            }
+            MultiSigRequestActionV1::SetActiveRequestsLimit {
+                active_requests_limit,
+            } => Self::SetActiveRequestsLimit {
+                active_requests_limit,
+            },
+            MultiSigRequestActionV1::Transfer { amount } => Self::Transfer {
+                amount,
+                metadata: PaymentMetadata::default(),
+            },
+            MultiSigRequestActionV1::NearEscrowTransfer {
+                receiver_id,
+                amount,
+                label,
+                is_cancellable,
+            } => Self::NearEscrowTransfer {
+                receiver_id,
+                amount,
+                metadata: PaymentMetadata::from_label(label),
+                is_cancellable,
+            },
+            MultiSigRequestActionV1::FTEscrowTransfer {
+                receiver_id,
+                amount,
+                token_id,
+                label,
+                is_cancellable,
+            } => Self::FTEscrowTransfer {
+                receiver_id,
+                amount,
+                token_id,
+                metadata: PaymentMetadata::from_label(label),
+                is_cancellable,
+            },
+        }
+    }
+}
+
+/// Request layout of the first release.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MultiSigRequestWithSignerV1 {
+    pub receiver_id: AccountId,
+    pub actions: Vec<MultiSigRequestActionV1>,
+    pub member: MultisigMember,
+    pub added_timestamp: u64,
+}
+
+impl From<MultiSigRequestWithSignerV1> for MultiSigRequestWithSigner {
+    fn from(request: MultiSigRequestWithSignerV1) -> Self {
+        Self {
+            request: MultiSigRequest {
+                receiver_id: request.receiver_id,
+                actions: request.actions.into_iter().map(Into::into).collect(),
+            },
+            member: request.member,
+            added_timestamp: request.added_timestamp,
+            executable_at: None,
+        }
+    }
+}
+
+/// State layout of the first release, stored without version.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub members: UnorderedSet<MultisigMember>,
+    pub num_confirmations: u32,
+    pub request_nonce: RequestId,
+    pub requests: UnorderedMap<RequestId, MultiSigRequestWithSignerV1>,
+    pub confirmations: LookupMap<RequestId, HashSet<String>>,
+    pub num_requests_pk: LookupMap<String, u32>,
+    pub active_requests_limit: u32,
+    pub near_committed_balance: u128,
+    pub ft_committed_balance: UnorderedMap<AccountId, u128>,
+    pub escrow_transfers: UnorderedMap<CryptoHash, EscrowTransferV1>,
+}
+
+/// State layouts `migrate` can upgrade from.
+pub enum VersionedState {
+    /// First release, stored without version.
+    V1(Box<ContractV1>),
+    V2(Box<Contract>),
+}
+
+impl VersionedState {
+    /// Reads the contract state in the layout of the stored version.
+    pub fn read() -> Self {
+        let version = env::storage_read(STATE_VERSION_KEY).map(|version| {
+            u32::try_from_slice(&version)
+                .unwrap_or_else(|_| env::panic_str("Failed to deserialize"))
+        });
+        match version {
+            None => Self::V1(Box::new(read_state())),
+            Some(STATE_VERSION) => Self::V2(Box::new(read_state())),
+            Some(_) => env::panic_str("Unknown state version"),
+        }
+    }
+
+    /// Upgrades the state to the current layout.
+    pub fn migrate(self) -> Contract {
+        match self {
+            Self::V1(old) => migrate_v1(*old),
+            Self::V2(contract) => *contract,
+        }
+    }
+}
+
+/// Records the layout of the state as the current one.
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &STATE_VERSION.try_to_vec().unwrap());
+}
+
+fn read_state<T: BorshDeserialize>() -> T {
+    env::state_read().unwrap_or_else(|| env::panic_str("Contract is not initialized"))
+}
+
+/// Converts the stored requests and escrow transfers, keys confirmations and request counters
+/// by typed `MultisigMember` instead of its JSON serialization and initializes new fields.
+fn migrate_v1(mut old: ContractV1) -> Contract {
+    // both layouts share the storage prefixes, old entries are cleared before writing new ones,
+    // requests are converted when read
+    let old_requests = old.requests.to_vec();
+    old.requests.clear();
+    let mut requests = UnorderedMap::new(StorageKeys::Requests);
+    let mut confirmations = LookupMap::new(StorageKeys::Confirmations);
+    for (request_id, request) in old_requests {
+        // confirmation times were not recorded, the time the request was added is used instead
+        let confirmed: HashMap<MultisigMember, u64> = old
+            .confirmations
+            .remove(&request_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|member| (parse_member(member), request.added_timestamp))
+            .collect();
+        requests.insert(
+            &request_id,
+            &VersionedMultiSigRequestWithSigner::V1(request),
+        );
+        confirmations.insert(&request_id, &confirmed);
+    }
+
+    // only members have request counters, they are removed along with the member
+    let mut num_requests_pk = LookupMap::new(StorageKeys::NumRequestsPk);
+    for member in old.members.iter() {
+        if let Some(num_requests) = old.num_requests_pk.remove(&member.to_string()) {
+            num_requests_pk.insert(&member, &num_requests);
+        }
+    }
+
+    // escrow transfers are converted when read
+    let old_escrow_transfers = old.escrow_transfers.to_vec();
+    old.escrow_transfers.clear();
+    let mut escrow_transfers = UnorderedMap::new(StorageKeys::EscrowTransfers);
+    for (id, transfer) in old_escrow_transfers {
+        escrow_transfers.insert(&id, &VersionedEscrowTransfer::V1(transfer));
+    }
+
+    write_state_version();
+    Contract {
+        members: old.members,
+        num_confirmations: old.num_confirmations,
+        request_nonce: old.request_nonce,
+        requests,
+        confirmations,
+        rejections: LookupMap::new(StorageKeys::Rejections),
+        num_requests_pk,
+        active_requests_limit: old.active_requests_limit,
+        executed_requests: LookupMap::new(StorageKeys::ExecutedRequests),
+        history: Vector::new(StorageKeys::History),
+        member_history: LookupMap::new(StorageKeys::MemberHistory),
+        request_ttl: None,
+        timelock_policy: None,
+        action_thresholds: UnorderedMap::new(StorageKeys::ActionThresholds),
+        amount_tiers: UnorderedMap::new(StorageKeys::AmountTiers),
+        near_committed_balance: old.near_committed_balance,
+        storage_reserve: DEFAULT_STORAGE_RESERVE,
+        ft_committed_balance: old.ft_committed_balance,
+        escrow_transfers,
+        tokens: UnorderedMap::new(StorageKeys::Tokens),
+        token_balances: LookupMap::new(StorageKeys::TokenBalances),
+        incoming_payments: Vector::new(StorageKeys::IncomingPayments),
+        incoming_payment_index: LookupMap::new(StorageKeys::IncomingPaymentIndex),
+        incoming_totals: LookupMap::new(StorageKeys::IncomingTotals),
+        staked_balance: UnorderedMap::new(StorageKeys::StakedBalances),
+        unstaked_balance: UnorderedMap::new(StorageKeys::UnstakedBalances),
+        budgets: UnorderedMap::new(StorageKeys::Budgets),
+        budget_spending: LookupMap::new(StorageKeys::BudgetSpending),
+    }
+}
+
+/// Parses a member stored as its JSON serialization.
+fn parse_member(member: &str) -> MultisigMember {
+    serde_json::from_str(member).unwrap_or_else(|_| env::panic_str("Failed to deserialize"))
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Upgrades the stored state to the current layout. Called after a self-targeted `DeployContract`.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        VersionedState::read().migrate()
+    }
+}
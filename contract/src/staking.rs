@@ -0,0 +1,172 @@
+use near_sdk::{is_promise_success, json_types::U128, near_bindgen};
+
+use crate::*;
+
+#[ext_contract(ext_staking_pool)]
+pub trait ExtStakingPool {
+    fn deposit_and_stake(&mut self);
+    fn unstake(&mut self, amount: U128);
+    fn unstake_all(&mut self);
+    fn withdraw(&mut self, amount: U128);
+    fn get_account_unstaked_balance(&self, account_id: AccountId) -> U128;
+}
+
+impl Contract {
+    /// Stores the tracked balances of given pool, forgetting pools left without balance.
+    fn set_pool_balances(&mut self, staking_pool_id: &AccountId, staked: u128, unstaked: u128) {
+        if staked == 0 && unstaked == 0 {
+            self.staked_balance.remove(staking_pool_id);
+            self.unstaked_balance.remove(staking_pool_id);
+        } else {
+            self.staked_balance.insert(staking_pool_id, &staked);
+            self.unstaked_balance.insert(staking_pool_id, &unstaked);
+        }
+    }
+
+    /// Builds the staking pool call for given staking action, followed by a callback tracking balances.
+    pub(crate) fn staking_promise(
+        &mut self,
+        request_id: RequestId,
+        staking_pool_id: AccountId,
+        action: MultiSigRequestAction,
+    ) -> Result<Promise, ContractError> {
+        let (kind, amount, promise) = match action {
+            MultiSigRequestAction::DepositAndStake { amount } => {
+                // check if there is enough balance accounting storage and committed balance
+                let available = self.spendable_balance();
+                if amount.0 > available {
+                    return Err(ContractError::InsufficientBalance {
+                        token_id: None,
+                        available,
+                        requested: amount.0,
+                    });
+                }
+                (
+                    ActionKind::DepositAndStake,
+                    amount,
+                    ext_staking_pool::ext(staking_pool_id.clone())
+                        .with_attached_deposit(amount.0)
+                        .deposit_and_stake(),
+                )
+            }
+            MultiSigRequestAction::Unstake { amount } => (
+                ActionKind::Unstake,
+                amount,
+                ext_staking_pool::ext(staking_pool_id.clone()).unstake(amount),
+            ),
+            // the unstaked amount is read from the pool once unstaked
+            MultiSigRequestAction::UnstakeAll => (
+                ActionKind::UnstakeAll,
+                U128(0),
+                ext_staking_pool::ext(staking_pool_id.clone()).unstake_all(),
+            ),
+            MultiSigRequestAction::Withdraw { amount } => (
+                ActionKind::Withdraw,
+                amount,
+                ext_staking_pool::ext(staking_pool_id.clone()).withdraw(amount),
+            ),
+            _ => return Err(ContractError::NotStakingAction),
+        };
+        Ok(promise.then(
+            Self::ext(env::current_account_id()).callback_staking_action(
+                request_id,
+                staking_pool_id,
+                kind,
+                amount,
+            ),
+        ))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[private]
+    #[handle_result]
+    pub fn callback_staking_action(
+        &mut self,
+        request_id: RequestId,
+        staking_pool_id: AccountId,
+        kind: ActionKind,
+        amount: U128,
+    ) -> Result<PromiseOrValue<MultiSigResponse>, ContractError> {
+        if !is_promise_success() {
+            return Err(ContractError::StakingPoolFailed {
+                staking_pool_id,
+                action: kind,
+            });
+        }
+
+        let staked = self.staked_balance.get(&staking_pool_id).unwrap_or(0);
+        let unstaked = self.unstaked_balance.get(&staking_pool_id).unwrap_or(0);
+
+        // rewards are not tracked, the staked principal can not go below zero
+        let (staked, unstaked) = match kind {
+            ActionKind::DepositAndStake => (staked + amount.0, unstaked),
+            ActionKind::Unstake => (staked.saturating_sub(amount.0), unstaked + amount.0),
+            ActionKind::UnstakeAll => {
+                return Ok(ext_staking_pool::ext(staking_pool_id.clone())
+                    .get_account_unstaked_balance(env::current_account_id())
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .callback_unstaked_balance(request_id, staking_pool_id),
+                    )
+                    .into());
+            }
+            ActionKind::Withdraw => (staked, unstaked.saturating_sub(amount.0)),
+            _ => return Err(ContractError::NotStakingAction),
+        };
+        self.set_pool_balances(&staking_pool_id, staked, unstaked);
+
+        Ok(PromiseOrValue::Value(MultiSigResponse::new(
+            request_id,
+            FuncResponse::Default(true),
+        )))
+    }
+
+    /// Tracks the unstaked balance reported by the pool after `UnstakeAll`, rewards included.
+    /// If the pool can't be queried, the tracked principal is moved to the unstaked balance.
+    #[private]
+    pub fn callback_unstaked_balance(
+        &mut self,
+        request_id: RequestId,
+        staking_pool_id: AccountId,
+        #[callback_result] unstaked: Result<U128, near_sdk::PromiseError>,
+    ) -> MultiSigResponse {
+        let unstaked = unstaked.map(|u| u.0).unwrap_or_else(|_| {
+            self.staked_balance.get(&staking_pool_id).unwrap_or(0)
+                + self.unstaked_balance.get(&staking_pool_id).unwrap_or(0)
+        });
+        self.set_pool_balances(&staking_pool_id, 0, unstaked);
+
+        MultiSigResponse::new(request_id, FuncResponse::Default(true))
+    }
+
+    /// Returns liquid, committed, staked and unstaked NEAR of the treasury.
+    pub fn get_treasury_balance(&self) -> TreasuryBalance {
+        let staking_pools: Vec<StakingPoolBalance> = self
+            .staked_balance
+            .iter()
+            .map(|(staking_pool_id, staked)| StakingPoolBalance {
+                unstaked: self
+                    .unstaked_balance
+                    .get(&staking_pool_id)
+                    .unwrap_or(0)
+                    .into(),
+                staking_pool_id,
+                staked: staked.into(),
+            })
+            .collect();
+        let staked: u128 = staking_pools.iter().map(|p| p.staked.0).sum();
+        let unstaked: u128 = staking_pools.iter().map(|p| p.unstaked.0).sum();
+        let liquid = env::account_balance();
+
+        TreasuryBalance {
+            liquid: liquid.into(),
+            committed: self.near_committed_balance.into(),
+            staked: staked.into(),
+            unstaked: unstaked.into(),
+            total: (liquid + staked + unstaked).into(),
+            staking_pools,
+        }
+    }
+}
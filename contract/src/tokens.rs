@@ -0,0 +1,221 @@
+use near_sdk::json_types::{U128, U64};
+use near_sdk::near_bindgen;
+
+use crate::*;
+
+impl Contract {
+    /// Fetches the metadata of given token, the token is registered once it is returned.
+    /// Registering a token again refreshes its metadata.
+    pub(crate) fn register_token(&self, request_id: RequestId, token_id: AccountId) -> Promise {
+        ext_nep141_token::ext(token_id.clone()).ft_metadata().then(
+            Self::ext(env::current_account_id()).callback_token_metadata(request_id, token_id),
+        )
+    }
+
+    /// Removes given token from the registry.
+    pub(crate) fn unregister_token(&mut self, token_id: AccountId) -> Result<(), ContractError> {
+        if self.ft_committed_balance.get(&token_id).unwrap_or(0) != 0 {
+            return Err(ContractError::CommittedBalance);
+        }
+        self.tokens.remove(&token_id);
+        self.token_balances.remove(&token_id);
+        Ok(())
+    }
+
+    /// Fetches the balances of given tokens, `callback_token_balance` caches them and flags shortfalls.
+    /// The promises are not joined: a joint promise can't be returned and one failing token
+    /// must not block the others.
+    fn fetch_token_balances(&self, token_ids: Vec<AccountId>) -> Result<(), ContractError> {
+        if token_ids.is_empty() {
+            return Err(ContractError::NoTokens);
+        }
+        for token_id in token_ids {
+            ext_nep141_token::ext(token_id.clone())
+                .ft_balance_of(env::current_account_id())
+                .then(Self::ext(env::current_account_id()).callback_token_balance(token_id));
+        }
+        Ok(())
+    }
+
+    /// Caches the balance of given token held by the multisig.
+    pub(crate) fn cache_token_balance(&mut self, token_id: &AccountId, balance: u128) {
+        self.token_balances.insert(
+            token_id,
+            &CachedBalance {
+                balance: balance.into(),
+                updated_at: env::block_timestamp().into(),
+            },
+        );
+    }
+
+    /// Returns balances of NEAR or given token, `total` being the balance held by the multisig.
+    fn asset_overview(
+        token_id: Option<AccountId>,
+        total: u128,
+        updated_at: Option<U64>,
+        committed: u128,
+        pending: u128,
+        spendable: u128,
+    ) -> AssetOverview {
+        AssetOverview {
+            token_id,
+            total: total.into(),
+            updated_at,
+            committed: committed.into(),
+            pending: pending.into(),
+            available: spendable.saturating_sub(pending).into(),
+        }
+    }
+
+    /// Fails if given token is not registered.
+    pub(crate) fn check_registered_token(&self, token_id: &AccountId) -> Result<(), ContractError> {
+        if self.tokens.get(token_id).is_none() {
+            return Err(ContractError::TokenNotRegistered(token_id.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[private]
+    #[handle_result]
+    pub fn callback_token_metadata(
+        &mut self,
+        request_id: RequestId,
+        token_id: AccountId,
+        #[callback_result] metadata: Result<TokenMetadata, near_sdk::PromiseError>,
+    ) -> Result<MultiSigResponse, ContractError> {
+        let metadata =
+            metadata.map_err(|_| ContractError::TokenMetadataUnavailable(token_id.clone()))?;
+        self.tokens.insert(&token_id, &Some(metadata));
+
+        Ok(MultiSigResponse::new(
+            request_id,
+            FuncResponse::Default(true),
+        ))
+    }
+
+    /// Fetches the balances of all registered tokens and caches them. Can be called by anyone.
+    #[handle_result]
+    pub fn refresh_token_balances(&mut self) -> Result<(), ContractError> {
+        self.fetch_token_balances(self.tokens.keys_as_vector().to_vec())
+    }
+
+    /// Checks the balances of all tokens with committed balances against their on-chain balances.
+    /// Shortfalls emit an event and are returned by `get_token_shortfalls`. Can be called by anyone.
+    #[handle_result]
+    pub fn reconcile_tokens(&mut self) -> Result<(), ContractError> {
+        self.fetch_token_balances(self.ft_committed_balance.keys_as_vector().to_vec())
+    }
+
+    #[private]
+    #[handle_result]
+    pub fn callback_token_balance(
+        &mut self,
+        token_id: AccountId,
+        #[callback_result] balance: Result<U128, near_sdk::PromiseError>,
+    ) -> Result<(), ContractError> {
+        let balance = balance
+            .map_err(|_| ContractError::TokenBalanceUnavailable(token_id.clone()))?
+            .0;
+
+        // tokens with committed balances are checked even if they were unregistered
+        // or committed before the registry existed
+        let committed = self.ft_committed_balance.get(&token_id).unwrap_or(0);
+        if self.tokens.get(&token_id).is_some() || committed != 0 {
+            self.cache_token_balance(&token_id, balance);
+        }
+
+        if committed > balance {
+            PaymentsEvent::CommittedBalanceShortfall {
+                token_id,
+                committed: committed.into(),
+                balance: balance.into(),
+            }
+            .emit();
+        }
+        Ok(())
+    }
+
+    /// Returns tokens of which the last cached balance is below the committed balance.
+    pub fn get_token_shortfalls(&self) -> Vec<TokenShortfall> {
+        self.ft_committed_balance
+            .iter()
+            .filter_map(|(token_id, committed)| {
+                let cached = self.token_balances.get(&token_id)?;
+                if committed <= cached.balance.0 {
+                    return None;
+                }
+                Some(TokenShortfall {
+                    token_id,
+                    committed: committed.into(),
+                    balance: cached.balance,
+                    shortfall: (committed - cached.balance.0).into(),
+                    checked_at: cached.updated_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns total, committed, pending and available balances of NEAR and every registered token.
+    /// Token balances are the ones cached by `refresh_token_balances`.
+    pub fn get_treasury_overview(&self) -> TreasuryOverview {
+        let mut pending = self.pending_outflow();
+        let near_pending = pending.remove(&None).unwrap_or(0);
+        TreasuryOverview {
+            near: Self::asset_overview(
+                None,
+                env::account_balance(),
+                None,
+                self.near_committed_balance,
+                near_pending,
+                self.spendable_balance(),
+            ),
+            tokens: self
+                .tokens
+                .keys()
+                .map(|token_id| {
+                    let cached = self.token_balances.get(&token_id);
+                    let total = cached.as_ref().map_or(0, |c| c.balance.0);
+                    let committed = self.ft_committed_balance.get(&token_id).unwrap_or(0);
+                    Self::asset_overview(
+                        Some(token_id.clone()),
+                        total,
+                        cached.map(|c| c.updated_at),
+                        committed,
+                        pending.get(&Some(token_id)).copied().unwrap_or(0),
+                        total.saturating_sub(committed),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns registered tokens with their cached metadata, `None` until fetched.
+    pub fn get_registered_tokens(&self) -> Vec<(AccountId, Option<TokenMetadata>)> {
+        self.tokens.to_vec()
+    }
+
+    /// Returns committed and received balances of registered tokens in human units.
+    pub fn get_token_balances(&self) -> Vec<TokenBalance> {
+        self.tokens
+            .iter()
+            .map(|(token_id, metadata)| {
+                let decimals = metadata.as_ref().map_or(0, |m| m.decimals);
+                TokenBalance {
+                    committed: format_amount(
+                        self.ft_committed_balance.get(&token_id).unwrap_or(0),
+                        decimals,
+                    ),
+                    received: format_amount(
+                        self.incoming_totals.get(&token_id).unwrap_or(0),
+                        decimals,
+                    ),
+                    symbol: metadata.map(|m| m.symbol),
+                    token_id,
+                }
+            })
+            .collect()
+    }
+}
@@ -72,6 +72,7 @@ mod tests {
         ) -> Result<Vec<(CryptoHash, EscrowTransfer)>> {
             let v: Vec<(CryptoHash, EscrowTransfer)> = caller
                 .call(self.contract.id(), "get_payments")
+                .args_json(json!({}))
                 .transact()
                 .await?
                 .json()?;
@@ -158,6 +159,19 @@ mod tests {
             )
             .await?;
 
+        info!("registering wrap near token");
+        contract_wrapper
+            .add_request_and_confirm(
+                &alice,
+                MultiSigRequest {
+                    receiver_id: workspace_acc_id_to_sdk_id(payments_contract.as_account()),
+                    actions: vec![MultiSigRequestAction::RegisterToken {
+                        token_id: workspace_acc_id_to_sdk_id(ft_contract.as_account()),
+                    }],
+                },
+            )
+            .await?;
+
         Ok((contract_wrapper, ft_contract, alice, bob))
     }
 
@@ -169,6 +183,10 @@ mod tests {
             receiver_id: AccountId::new_unchecked(to.id().to_string()),
             actions: vec![MultiSigRequestAction::Transfer {
                 amount: ONE_NEAR.into(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
             }],
         };
 
@@ -188,7 +206,10 @@ mod tests {
             actions: vec![MultiSigRequestAction::NearEscrowTransfer {
                 receiver_id: workspace_acc_id_to_sdk_id(&to),
                 amount: ONE_NEAR.into(),
-                label: "test".to_string(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
                 is_cancellable: true,
             }],
         };
@@ -209,7 +230,7 @@ mod tests {
     }
 
     #[tokio::test]
-    #[should_panic = "Not enough NEAR balance"]
+    #[should_panic = "InsufficientBalance"]
     async fn test_escrow_transfer_above_account_balance() {
         let (contract_wrapper, _, caller, to) = init().await.unwrap();
 
@@ -218,7 +239,10 @@ mod tests {
             actions: vec![MultiSigRequestAction::NearEscrowTransfer {
                 receiver_id: workspace_acc_id_to_sdk_id(&to),
                 amount: (90 * ONE_NEAR).into(),
-                label: "test".to_string(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
                 is_cancellable: true,
             }],
         };
@@ -239,7 +263,10 @@ mod tests {
             actions: vec![MultiSigRequestAction::NearEscrowTransfer {
                 receiver_id: workspace_acc_id_to_sdk_id(&to),
                 amount: (90 * ONE_NEAR).into(),
-                label: "test".to_string(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
                 is_cancellable: true,
             }],
         };
@@ -265,7 +292,10 @@ mod tests {
             actions: vec![MultiSigRequestAction::FTEscrowTransfer {
                 receiver_id: workspace_acc_id_to_sdk_id(&to),
                 amount: (30 * ONE_NEAR).into(),
-                label: "test".to_string(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
                 is_cancellable: true,
                 token_id: workspace_acc_id_to_sdk_id(ft_contract.as_account()),
             }],
@@ -301,16 +331,18 @@ mod tests {
     }
 
     #[tokio::test]
-    #[should_panic = "Not enough"]
-    async fn test_ft_escrow_transfer_above_balance() {
-        let (contract_wrapper, ft_contract, caller, to) = init().await.unwrap();
+    async fn test_ft_escrow_transfer_above_balance() -> Result<()> {
+        let (contract_wrapper, ft_contract, caller, to) = init().await?;
 
         let request = MultiSigRequest {
             receiver_id: workspace_acc_id_to_sdk_id(&to),
             actions: vec![MultiSigRequestAction::FTEscrowTransfer {
                 receiver_id: workspace_acc_id_to_sdk_id(&to),
                 amount: (30 * ONE_NEAR).into(),
-                label: "test".to_string(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
                 is_cancellable: true,
                 token_id: workspace_acc_id_to_sdk_id(ft_contract.as_account()),
             }],
@@ -326,16 +358,85 @@ mod tests {
             actions: vec![MultiSigRequestAction::FTEscrowTransfer {
                 receiver_id: workspace_acc_id_to_sdk_id(&to),
                 amount: (30 * ONE_NEAR).into(),
-                label: "test".to_string(),
+                metadata: PaymentMetadata {
+                    category: Some("operations".to_string()),
+                    ..Default::default()
+                },
                 is_cancellable: true,
                 token_id: workspace_acc_id_to_sdk_id(ft_contract.as_account()),
             }],
         };
 
-        contract_wrapper
+        // the escrow callback fails, the outcome is recorded instead of failing the transaction
+        let ret = contract_wrapper
             .add_request_and_confirm(&caller, request)
-            .await
-            .unwrap();
+            .await?
+            .expect("no response");
+        assert!(matches!(ret.response, FuncResponse::Default(false)));
+
+        let outcome: Option<ExecutionOutcome> = contract_wrapper
+            .contract
+            .view("get_execution_outcome")
+            .args_json(json!({ "request_id": ret.request_id }))
+            .await?
+            .json()?;
+        assert!(!outcome.expect("no outcome").success);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_staking() -> Result<()> {
+        let (contract_wrapper, _, caller, pool) = init().await?;
+
+        info!("deploying mock staking pool");
+        let pool = pool
+            .deploy(&fs::read(
+                "../target/wasm32-unknown-unknown/release/mock_staking_pool.wasm",
+            )?)
+            .await?
+            .into_result()?;
+        pool.call("new").transact().await?.into_result()?;
+        let pool_id = workspace_acc_id_to_sdk_id(pool.as_account());
+
+        for action in [
+            MultiSigRequestAction::DepositAndStake {
+                amount: (5 * ONE_NEAR).into(),
+            },
+            MultiSigRequestAction::Unstake {
+                amount: (2 * ONE_NEAR).into(),
+            },
+            MultiSigRequestAction::Withdraw {
+                amount: ONE_NEAR.into(),
+            },
+        ] {
+            let request = MultiSigRequest {
+                receiver_id: pool_id.clone(),
+                actions: vec![action],
+            };
+            contract_wrapper
+                .add_request_and_confirm(&caller, request)
+                .await?;
+        }
+
+        let balance: TreasuryBalance = contract_wrapper
+            .contract
+            .view("get_treasury_balance")
+            .await?
+            .json()?;
+        assert_eq!(balance.staked.0, 3 * ONE_NEAR);
+        assert_eq!(balance.unstaked.0, ONE_NEAR);
+        assert_eq!(balance.staking_pools.len(), 1);
+        assert_eq!(balance.staking_pools[0].staking_pool_id, pool_id);
+
+        let staked: U128 = pool
+            .view("get_account_staked_balance")
+            .args_json(json!({ "account_id": contract_wrapper.contract.id() }))
+            .await?
+            .json()?;
+        assert_eq!(staked.0, 3 * ONE_NEAR);
+
+        Ok(())
     }
 
     // Helper function to convert workspaces::AccountId to near_sdk::AccountId
@@ -0,0 +1,65 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Promise};
+
+/// Staking pool without epochs or rewards: unstaked balance can be withdrawn right away.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct MockStakingPool {
+    /// Maps accounts to (staked, unstaked) balances.
+    accounts: LookupMap<AccountId, (u128, u128)>,
+}
+
+#[near_bindgen]
+impl MockStakingPool {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            accounts: LookupMap::new(b"a"),
+        }
+    }
+
+    #[payable]
+    pub fn deposit_and_stake(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let (staked, unstaked) = self.get_account(&account_id);
+        self.accounts
+            .insert(&account_id, &(staked + env::attached_deposit(), unstaked));
+    }
+
+    pub fn unstake(&mut self, amount: U128) {
+        let account_id = env::predecessor_account_id();
+        let (staked, unstaked) = self.get_account(&account_id);
+        assert!(amount.0 <= staked, "Not enough staked balance");
+        self.accounts
+            .insert(&account_id, &(staked - amount.0, unstaked + amount.0));
+    }
+
+    pub fn unstake_all(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let (staked, unstaked) = self.get_account(&account_id);
+        self.accounts.insert(&account_id, &(0, unstaked + staked));
+    }
+
+    pub fn withdraw(&mut self, amount: U128) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let (staked, unstaked) = self.get_account(&account_id);
+        assert!(amount.0 <= unstaked, "Not enough unstaked balance");
+        self.accounts
+            .insert(&account_id, &(staked, unstaked - amount.0));
+        Promise::new(account_id).transfer(amount.0)
+    }
+
+    pub fn get_account_staked_balance(&self, account_id: AccountId) -> U128 {
+        self.get_account(&account_id).0.into()
+    }
+
+    pub fn get_account_unstaked_balance(&self, account_id: AccountId) -> U128 {
+        self.get_account(&account_id).1.into()
+    }
+
+    fn get_account(&self, account_id: &AccountId) -> (u128, u128) {
+        self.accounts.get(account_id).unwrap_or((0, 0))
+    }
+}
@@ -0,0 +1,139 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, serde_json, AccountId, Balance, FunctionError};
+
+use crate::{ActionKind, RequestId};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, PartialEq, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize))]
+#[serde(crate = "near_sdk::serde")]
+pub enum ContractError {
+    InsufficientDeposit {
+        expected: Balance,
+        received: Balance,
+    },
+    EscrowTransferNotFound(String),
+    NotAuthorized,
+    NearTransferFailed,
+
+    /// Members
+    ///
+    /// Caller (predecessor or signer) is not a member of the multisig.
+    NotAMember,
+    /// Number of confirmations is zero or above the number of members.
+    InvalidNumConfirmations {
+        num_confirmations: u32,
+        num_members: u64,
+    },
+    /// Removing the member would leave fewer members than some request requires.
+    TooFewMembers,
+    /// Keys of members are removed with `DeleteMember`.
+    KeyBelongsToMember,
+
+    /// Requests
+    ///
+    /// No active request with given id: either wrong id or already executed.
+    RequestNotFound(RequestId),
+    TooManyActiveRequests {
+        limit: u32,
+    },
+    /// Requests can't be deleted during the request cooldown after creation.
+    RequestCooldown,
+    RequestNotExpired,
+    /// Expired requests can only be deleted.
+    RequestExpired,
+    /// Queued requests can only be executed or vetoed.
+    RequestQueued,
+    RequestNotQueued,
+    TimelockNotPassed {
+        executable_at: u64,
+    },
+    /// The action only works when the receiver is the multisig itself.
+    NotSelfRequest,
+    NotStakingAction,
+    /// The action must be the only one of its request.
+    NotSingleAction,
+    /// Amount tiers must be sorted by strictly increasing `min_amount`.
+    InvalidAmountTiers,
+    InvalidRequestTtl,
+
+    /// Confirmations
+    ///
+    /// The caller already confirmed the request.
+    AlreadyConfirmed,
+    AlreadyRejected,
+    NotConfirmed,
+    /// The caller must revoke their confirmation before rejecting.
+    ConfirmationNotRevoked,
+
+    /// Escrow and balances
+    ///
+    /// `token_id` is `None` for NEAR.
+    InsufficientBalance {
+        token_id: Option<AccountId>,
+        available: Balance,
+        requested: Balance,
+    },
+    PendingEscrowTransfers,
+    CommittedBalance,
+    StakedBalance,
+
+    /// Payment metadata
+    ///
+    /// A text field of the metadata is longer than `max_len` bytes.
+    MetadataTooLong {
+        field: String,
+        max_len: u32,
+    },
+    TooManyTags {
+        max_tags: u32,
+    },
+    /// Payments must name a category of the budgets.
+    MissingCategory,
+
+    /// Budgets
+    ///
+    /// The payments of the request exceed what is left of the budget in its current period.
+    BudgetExceeded {
+        category: String,
+        token_id: Option<AccountId>,
+        remaining: Balance,
+        requested: Balance,
+    },
+
+    /// Tokens
+    ///
+    /// Only registered tokens can be escrowed or received.
+    TokenNotRegistered(AccountId),
+    /// The message of an incoming payment is longer than `max_len` bytes.
+    MemoTooLong {
+        max_len: u32,
+    },
+    /// The total received of the token would overflow.
+    IncomingTotalOverflow(AccountId),
+    /// There are no tokens to fetch balances of.
+    NoTokens,
+    /// The token contract failed to return the balance of the multisig.
+    TokenBalanceUnavailable(AccountId),
+    /// The token contract failed to return its metadata.
+    TokenMetadataUnavailable(AccountId),
+
+    /// Staking
+    ///
+    /// The staking pool call of a staking action failed.
+    StakingPoolFailed {
+        staking_pool_id: AccountId,
+        action: ActionKind,
+    },
+
+    /// History
+    ///
+    /// No archived request at given index.
+    HistoryEntryNotFound(u64),
+}
+
+impl FunctionError for ContractError {
+    fn panic(&self) -> ! {
+        env::panic_str(&serde_json::to_string(self).unwrap_or(format!("serde failed: {self:?}")))
+    }
+}
@@ -0,0 +1,151 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base58CryptoHash, U128, U64};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, serde_json, AccountId};
+
+use crate::{ActionKind, MultiSigRequestAction, MultisigMember, PaymentMetadata, RequestId};
+
+/// Standard name of the events emitted by the payments contract.
+pub const EVENT_STANDARD: &str = "near-payments";
+
+/// Version of the event format.
+pub const EVENT_VERSION: &str = "1.0.0";
+
+/// Why a request was removed without being executed.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum DeletionReason {
+    /// Deleted by a member after the cooldown.
+    Deleted,
+    /// Garbage collected after its TTL.
+    Expired,
+    /// Closed because enough members rejected it.
+    Rejected,
+    /// Vetoed while queued behind the timelock.
+    Vetoed,
+    /// Removed along with the member who proposed it.
+    MemberRemoved,
+}
+
+/// Events emitted by the payments contract, following NEP-297.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(
+    crate = "near_sdk::serde",
+    tag = "event",
+    content = "data",
+    rename_all = "snake_case"
+)]
+pub enum PaymentsEvent {
+    /// The request itself is returned by `get_request`, contract code can't fit in a log.
+    RequestAdded {
+        request_id: RequestId,
+        member: MultisigMember,
+        receiver_id: AccountId,
+        actions: Vec<ActionKind>,
+    },
+    RequestConfirmed {
+        request_id: RequestId,
+        member: MultisigMember,
+    },
+    ConfirmationRevoked {
+        request_id: RequestId,
+        member: MultisigMember,
+    },
+    RequestRejected {
+        request_id: RequestId,
+        member: MultisigMember,
+        /// True if the request was closed because it can no longer be confirmed.
+        closed: bool,
+    },
+    RequestQueued {
+        request_id: RequestId,
+        executable_at: U64,
+    },
+    RequestDeleted {
+        request_id: RequestId,
+        reason: DeletionReason,
+    },
+    RequestExecuted {
+        request_id: RequestId,
+        success: bool,
+    },
+    MemberAdded {
+        member: MultisigMember,
+    },
+    MemberRemoved {
+        member: MultisigMember,
+    },
+    ConfigChanged {
+        change: MultiSigRequestAction,
+    },
+    EscrowCreated {
+        payment_id: Base58CryptoHash,
+        receiver_id: AccountId,
+        amount: U128,
+        /// `None` for NEAR.
+        token_id: Option<AccountId>,
+        metadata: PaymentMetadata,
+    },
+    EscrowClaimed {
+        payment_id: Base58CryptoHash,
+        receiver_id: AccountId,
+        amount: U128,
+        /// `None` for NEAR.
+        token_id: Option<AccountId>,
+    },
+    CommittedBalanceChanged {
+        /// `None` for NEAR.
+        token_id: Option<AccountId>,
+        committed: U128,
+    },
+    /// Tokens received through `ft_transfer_call`.
+    PaymentReceived {
+        token_id: AccountId,
+        sender_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    },
+    /// The multisig holds less of a token than it committed in escrow transfers.
+    CommittedBalanceShortfall {
+        token_id: AccountId,
+        committed: U128,
+        balance: U128,
+    },
+}
+
+/// NEP-297 envelope of a `PaymentsEvent`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: PaymentsEvent,
+}
+
+impl PaymentsEvent {
+    /// Logs the event as `EVENT_JSON:{...}`.
+    pub fn emit(self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD.to_string(),
+            version: EVENT_VERSION.to_string(),
+            event: self,
+        };
+        env::log_str(&format!(
+            "EVENT_JSON:{}",
+            serde_json::to_string(&log).unwrap_or_else(|_| env::panic_str("Failed to serialize"))
+        ));
+    }
+}
+
+impl EventLog {
+    /// Parses a log line emitted with `PaymentsEvent::emit`, `None` for any other log.
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log: Self = serde_json::from_str(log.strip_prefix("EVENT_JSON:")?).ok()?;
+        if log.standard == EVENT_STANDARD {
+            Some(log)
+        } else {
+            None
+        }
+    }
+}
@@ -1,13 +1,30 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::json_types::{Base58CryptoHash, Base64VecU8, U128};
+use near_sdk::json_types::{Base58CryptoHash, Base64VecU8, U128, U64};
 use near_sdk::{env, serde_json, CryptoHash};
 use near_sdk::{
     serde::{Deserialize, Serialize},
     AccountId, PublicKey,
 };
 
+pub mod errors;
+pub mod events;
+pub use errors::*;
+pub use events::*;
+
 /// Represents member of the multsig: either account or access key to given account.
-#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    BorshDeserialize,
+    BorshSerialize,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
 #[serde(crate = "near_sdk::serde", untagged)]
 pub enum MultisigMember {
     AccessKey { public_key: PublicKey },
@@ -32,6 +49,7 @@ pub struct FunctionCallPermission {
 }
 
 /// Lowest level action that can be performed by the multisig contract.
+/// New actions are added at the end to keep stored requests readable.
 #[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(tag = "type", crate = "near_sdk::serde")]
 pub enum MultiSigRequestAction {
@@ -59,22 +77,343 @@ pub enum MultiSigRequestAction {
     SetActiveRequestsLimit { active_requests_limit: u32 },
     /// Payment options
     /// Transfers given amount to receiver.
-    Transfer { amount: U128 },
+    Transfer {
+        amount: U128,
+        metadata: PaymentMetadata,
+    },
     /// NEAR Escrow transfer
+    /// Can not be bundled with any other actions or transactions.
     NearEscrowTransfer {
         receiver_id: AccountId,
         amount: U128,
-        label: String,
+        metadata: PaymentMetadata,
         is_cancellable: bool,
     },
     /// FT Escrow transfer
+    /// Can not be bundled with any other actions or transactions.
     FTEscrowTransfer {
         receiver_id: AccountId,
         amount: U128,
         token_id: AccountId,
-        label: String,
+        metadata: PaymentMetadata,
         is_cancellable: bool,
     },
+    /// Sets number of confirmations required for requests containing given action type.
+    /// `None` removes the threshold so the action falls back to `num_confirmations`.
+    /// Can not be bundled with any other actions or transactions.
+    SetActionThreshold {
+        action: ActionKind,
+        num_confirmations: Option<u32>,
+    },
+    /// Sets amount tiers for given token, `None` for NEAR. Empty list removes the tiers.
+    /// Can not be bundled with any other actions or transactions.
+    SetAmountTiers {
+        token_id: Option<AccountId>,
+        tiers: Vec<AmountTier>,
+    },
+    /// Sets time in nanoseconds after which unconfirmed requests expire, `None` disables expiry.
+    /// Expired requests can not be confirmed and can be removed by anyone.
+    SetRequestTtl { request_ttl: Option<U64> },
+    /// Sets the timelock applied to high impact requests, `None` executes them right away.
+    /// Can not be bundled with any other actions or transactions.
+    SetTimelockPolicy { policy: Option<TimelockPolicy> },
+    /// Staking options, the receiver is the staking pool.
+    /// Deposits and stakes given amount of NEAR.
+    DepositAndStake { amount: U128 },
+    /// Unstakes given amount of staked NEAR.
+    Unstake { amount: U128 },
+    /// Unstakes all staked NEAR.
+    UnstakeAll,
+    /// Withdraws given amount of unstaked NEAR back to the multisig.
+    Withdraw { amount: U128 },
+    /// Deletes access key from the multisig account, the receiver must be the multisig.
+    /// Keys of multisig members must be removed with `DeleteMember`.
+    DeleteKey { public_key: PublicKey },
+    /// Deletes the multisig account and sends remaining balance to the beneficiary, the receiver must be the multisig.
+    /// Refused while the multisig holds escrow transfers or committed balances.
+    DeleteAccount { beneficiary_id: AccountId },
+    /// Registers given token contract and caches its metadata.
+    /// Only registered tokens can be used in payments and sent to the multisig with `ft_transfer_call`.
+    /// Can not be bundled with any other actions or transactions.
+    RegisterToken { token_id: AccountId },
+    /// Removes given token contract from the registry.
+    UnregisterToken { token_id: AccountId },
+    /// Sets NEAR kept on top of storage staking, payments can't spend it.
+    /// Can not be bundled with any other actions or transactions.
+    SetStorageReserve { storage_reserve: U128 },
+    /// Sets the budget of payments with given category in given token, `None` for NEAR.
+    /// `None` removes the budget, payments of the category are then not limited.
+    /// Can not be bundled with any other actions or transactions.
+    SetBudget {
+        category: String,
+        token_id: Option<AccountId>,
+        budget: Option<Budget>,
+    },
+}
+
+/// Number of confirmations required by an amount tier.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RequiredConfirmations {
+    Count(u32),
+    AllMembers,
+}
+
+/// Confirmations required once the outflow of a token in a request reaches `min_amount`.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AmountTier {
+    pub min_amount: U128,
+    pub required: RequiredConfirmations,
+}
+
+/// Calendar period over which a budget is spent, in UTC.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    Month,
+    Quarter,
+    Year,
+}
+
+/// Amount payments of a category can spend per period, in raw units of the token.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Budget {
+    pub amount: U128,
+    pub period: BudgetPeriod,
+}
+
+/// Budget with the amount spent in its current period.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BudgetStatus {
+    pub category: String,
+    /// `None` for NEAR.
+    pub token_id: Option<AccountId>,
+    pub budget: Budget,
+    /// Bounds of the current period in nanoseconds, `period_end` excluded.
+    pub period_start: U64,
+    pub period_end: U64,
+    pub spent: U128,
+    pub remaining: U128,
+}
+
+/// Grace period between the final confirmation and the execution of high impact requests:
+/// member changes, contract deployments, changes of confirmation thresholds or of the timelock itself,
+/// and large NEAR transfers.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimelockPolicy {
+    /// Time in nanoseconds before a queued request can be executed.
+    pub duration: U64,
+    /// Requests moving at least this amount of NEAR, or any amount of a token, are timelocked.
+    pub transfer_threshold: U128,
+}
+
+/// Type of a `MultiSigRequestAction`, used to key per action thresholds.
+/// Kinds follow the order of the actions, new kinds are added at the end.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    AddMember,
+    DeleteMember,
+    AddKey,
+    SetNumConfirmations,
+    SetActiveRequestsLimit,
+    Transfer,
+    NearEscrowTransfer,
+    FTEscrowTransfer,
+    SetActionThreshold,
+    SetAmountTiers,
+    SetRequestTtl,
+    SetTimelockPolicy,
+    DepositAndStake,
+    Unstake,
+    UnstakeAll,
+    Withdraw,
+    DeleteKey,
+    DeleteAccount,
+    RegisterToken,
+    UnregisterToken,
+    SetStorageReserve,
+    SetBudget,
+}
+
+impl ActionKind {
+    /// Returns true for actions changing the multisig configuration.
+    pub fn is_config(&self) -> bool {
+        matches!(
+            self,
+            ActionKind::SetNumConfirmations
+                | ActionKind::SetActionThreshold
+                | ActionKind::SetAmountTiers
+                | ActionKind::SetActiveRequestsLimit
+                | ActionKind::SetRequestTtl
+                | ActionKind::SetTimelockPolicy
+                | ActionKind::RegisterToken
+                | ActionKind::UnregisterToken
+                | ActionKind::SetStorageReserve
+                | ActionKind::SetBudget
+        )
+    }
+}
+
+impl MultiSigRequestAction {
+    /// Returns the type of the action.
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            MultiSigRequestAction::CreateAccount => ActionKind::CreateAccount,
+            MultiSigRequestAction::DeployContract { .. } => ActionKind::DeployContract,
+            MultiSigRequestAction::AddMember { .. } => ActionKind::AddMember,
+            MultiSigRequestAction::DeleteMember { .. } => ActionKind::DeleteMember,
+            MultiSigRequestAction::AddKey { .. } => ActionKind::AddKey,
+            MultiSigRequestAction::SetNumConfirmations { .. } => ActionKind::SetNumConfirmations,
+            MultiSigRequestAction::SetActiveRequestsLimit { .. } => {
+                ActionKind::SetActiveRequestsLimit
+            }
+            MultiSigRequestAction::Transfer { .. } => ActionKind::Transfer,
+            MultiSigRequestAction::NearEscrowTransfer { .. } => ActionKind::NearEscrowTransfer,
+            MultiSigRequestAction::FTEscrowTransfer { .. } => ActionKind::FTEscrowTransfer,
+            MultiSigRequestAction::SetActionThreshold { .. } => ActionKind::SetActionThreshold,
+            MultiSigRequestAction::SetAmountTiers { .. } => ActionKind::SetAmountTiers,
+            MultiSigRequestAction::SetRequestTtl { .. } => ActionKind::SetRequestTtl,
+            MultiSigRequestAction::SetTimelockPolicy { .. } => ActionKind::SetTimelockPolicy,
+            MultiSigRequestAction::DepositAndStake { .. } => ActionKind::DepositAndStake,
+            MultiSigRequestAction::Unstake { .. } => ActionKind::Unstake,
+            MultiSigRequestAction::UnstakeAll => ActionKind::UnstakeAll,
+            MultiSigRequestAction::Withdraw { .. } => ActionKind::Withdraw,
+            MultiSigRequestAction::DeleteKey { .. } => ActionKind::DeleteKey,
+            MultiSigRequestAction::DeleteAccount { .. } => ActionKind::DeleteAccount,
+            MultiSigRequestAction::RegisterToken { .. } => ActionKind::RegisterToken,
+            MultiSigRequestAction::UnregisterToken { .. } => ActionKind::UnregisterToken,
+            MultiSigRequestAction::SetStorageReserve { .. } => ActionKind::SetStorageReserve,
+            MultiSigRequestAction::SetBudget { .. } => ActionKind::SetBudget,
+        }
+    }
+
+    /// Returns the metadata of payment actions.
+    pub fn metadata(&self) -> Option<&PaymentMetadata> {
+        match self {
+            MultiSigRequestAction::Transfer { metadata, .. }
+            | MultiSigRequestAction::NearEscrowTransfer { metadata, .. }
+            | MultiSigRequestAction::FTEscrowTransfer { metadata, .. } => Some(metadata),
+            _ => None,
+        }
+    }
+
+    /// Returns the payment made by payment actions of a request sent to given receiver.
+    pub fn payment(&self, request_receiver_id: &AccountId) -> Option<PaymentSummary> {
+        let (receiver_id, token_id, amount, metadata) = match self {
+            MultiSigRequestAction::Transfer { amount, metadata } => {
+                (request_receiver_id, None, amount, metadata)
+            }
+            MultiSigRequestAction::NearEscrowTransfer {
+                receiver_id,
+                amount,
+                metadata,
+                ..
+            } => (receiver_id, None, amount, metadata),
+            MultiSigRequestAction::FTEscrowTransfer {
+                receiver_id,
+                amount,
+                token_id,
+                metadata,
+                ..
+            } => (receiver_id, Some(token_id.clone()), amount, metadata),
+            _ => return None,
+        };
+        Some(PaymentSummary {
+            receiver_id: receiver_id.clone(),
+            token_id,
+            amount: *amount,
+            metadata: metadata.clone(),
+        })
+    }
+}
+
+impl MultiSigRequest {
+    /// Returns the payments made by the request.
+    pub fn payments(&self) -> Vec<PaymentSummary> {
+        self.actions
+            .iter()
+            .filter_map(|action| action.payment(&self.receiver_id))
+            .collect()
+    }
+
+    /// Returns the request as kept in the history, without the code of `DeployContract`.
+    pub fn without_code(mut self) -> Self {
+        for action in &mut self.actions {
+            if let MultiSigRequestAction::DeployContract { code } = action {
+                *code = Base64VecU8(vec![]);
+            }
+        }
+        self
+    }
+}
+
+/// Payment made by a request.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PaymentSummary {
+    /// Receiver of the transfer or of the escrow payment.
+    pub receiver_id: AccountId,
+    /// `None` for NEAR.
+    pub token_id: Option<AccountId>,
+    pub amount: U128,
+    pub metadata: PaymentMetadata,
+}
+
+/// Accounting details of a payment. Text fields are limited in size by the contract.
+#[derive(
+    Debug, Default, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct PaymentMetadata {
+    /// Free-form description, labels of payments made before metadata are kept here.
+    pub label: Option<String>,
+    pub invoice_number: Option<String>,
+    /// Cost category, e.g. a department or an account of the chart of accounts.
+    /// Required by the contract, payments are charged to the budget of their category.
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    /// Due date in nanoseconds.
+    pub due_date: Option<U64>,
+    /// Reference in an external system, e.g. an accounting or payroll tool.
+    pub external_reference: Option<String>,
+}
+
+impl PaymentMetadata {
+    /// Metadata of a payment made with a free-form label.
+    pub fn from_label(label: String) -> Self {
+        Self {
+            label: Some(label).filter(|label| !label.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if the metadata has given category and tag, `None` matching any.
+    // `Option::is_none_or` needs a newer toolchain than the contract is built with
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn matches(&self, category: Option<&str>, tag: Option<&str>) -> bool {
+        category.map_or(true, |category| self.category.as_deref() == Some(category))
+            && tag.map_or(true, |tag| self.tags.iter().any(|t| t == tag))
+    }
 }
 
 /// The request the user makes specifying the receiving account and actions they want to execute (1 tx)
@@ -93,12 +432,25 @@ pub enum FuncResponse {
     Default(bool),
     EscrowPayment(Base58CryptoHash),
     Balance(U128),
+    /// Request is queued and can be executed after given timestamp.
+    Queued(U64),
 }
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Clone))]
 #[serde(crate = "near_sdk::serde")]
 pub struct EscrowTransfer {
+    pub id: CryptoHash,
+    pub receiver_id: AccountId,
+    pub amount: u128,
+    pub metadata: PaymentMetadata,
+    pub is_locked: bool,
+    pub token_account: Option<AccountId>,
+}
+
+/// Layout of `EscrowTransfer` before payment metadata.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct EscrowTransferV1 {
     pub id: CryptoHash,
     pub receiver_id: AccountId,
     pub amount: u128,
@@ -107,6 +459,259 @@ pub struct EscrowTransfer {
     pub token_account: Option<AccountId>,
 }
 
+/// Contract source metadata, following NEP-330.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSourceMetadata {
+    pub version: Option<String>,
+    pub link: Option<String>,
+    pub standards: Vec<Standard>,
+}
+
+/// NEP implemented by the contract.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Standard {
+    pub standard: String,
+    pub version: String,
+}
+
+/// Full configuration of the multisig.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractConfig {
+    pub num_confirmations: u32,
+    pub active_requests_limit: u32,
+    /// Time in nanoseconds before a request can be deleted.
+    pub request_cooldown: U64,
+    pub num_members: u64,
+    pub request_ttl: Option<U64>,
+    pub timelock_policy: Option<TimelockPolicy>,
+    pub action_thresholds: Vec<(ActionKind, u32)>,
+    /// Amount tiers per token, `None` being NEAR.
+    pub amount_tiers: Vec<(Option<AccountId>, Vec<AmountTier>)>,
+    /// NEAR kept on top of storage staking.
+    pub storage_reserve: U128,
+    pub state_version: u32,
+}
+
+/// Stored layouts of `EscrowTransfer`, new layouts are added as new variants.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedEscrowTransfer {
+    V1(EscrowTransferV1),
+    V2(EscrowTransfer),
+}
+
+impl From<VersionedEscrowTransfer> for EscrowTransfer {
+    fn from(transfer: VersionedEscrowTransfer) -> Self {
+        match transfer {
+            VersionedEscrowTransfer::V1(transfer) => Self {
+                id: transfer.id,
+                receiver_id: transfer.receiver_id,
+                amount: transfer.amount,
+                metadata: PaymentMetadata::from_label(transfer.label),
+                is_locked: transfer.is_locked,
+                token_account: transfer.token_account,
+            },
+            VersionedEscrowTransfer::V2(transfer) => transfer,
+        }
+    }
+}
+
+impl From<EscrowTransfer> for VersionedEscrowTransfer {
+    fn from(transfer: EscrowTransfer) -> Self {
+        Self::V2(transfer)
+    }
+}
+
+/// NEAR held with a staking pool, as tracked by the multisig.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakingPoolBalance {
+    pub staking_pool_id: AccountId,
+    /// Staked principal, rewards are not included.
+    pub staked: U128,
+    /// Unstaked NEAR not withdrawn yet.
+    pub unstaked: U128,
+}
+
+/// NEAR held by the treasury.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryBalance {
+    /// Balance of the multisig account, committed balance included.
+    pub liquid: U128,
+    /// Balance committed in escrow transfers.
+    pub committed: U128,
+    /// Staked principal over all staking pools.
+    pub staked: U128,
+    /// Unstaked NEAR waiting to be withdrawn over all staking pools.
+    pub unstaked: U128,
+    /// Sum of liquid, staked and unstaked balances.
+    pub total: U128,
+    pub staking_pools: Vec<StakingPoolBalance>,
+}
+
+/// Balance of a token held by the multisig, cached from `ft_balance_of`.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CachedBalance {
+    pub balance: U128,
+    pub updated_at: U64,
+}
+
+/// Token of which the multisig holds less than it committed in escrow transfers.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenShortfall {
+    pub token_id: AccountId,
+    pub committed: U128,
+    /// Balance cached by the last reconciliation or refresh.
+    pub balance: U128,
+    pub shortfall: U128,
+    pub checked_at: U64,
+}
+
+/// Balances of NEAR or a registered token, in raw units.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetOverview {
+    /// `None` for NEAR.
+    pub token_id: Option<AccountId>,
+    /// Balance of the multisig account for NEAR, last cached balance for tokens.
+    pub total: U128,
+    /// When the token balance was cached, `None` for NEAR or if it was never fetched.
+    pub updated_at: Option<U64>,
+    /// Balance committed in escrow transfers.
+    pub committed: U128,
+    /// Amount requested by active requests.
+    pub pending: U128,
+    /// Amount left once committed and pending amounts are paid,
+    /// storage staking and the storage reserve excluded for NEAR.
+    pub available: U128,
+}
+
+/// Balances of NEAR and every registered token.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryOverview {
+    pub near: AssetOverview,
+    pub tokens: Vec<AssetOverview>,
+}
+
+/// Metadata of a registered token, cached from `ft_metadata`.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Balances of a registered token in human units.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenBalance {
+    pub token_id: AccountId,
+    /// `None` until the token metadata is fetched, amounts are then shown without decimals.
+    pub symbol: Option<String>,
+    /// Balance committed in escrow transfers.
+    pub committed: String,
+    /// Total received through `ft_transfer_call`.
+    pub received: String,
+}
+
+/// Formats a raw token amount in human units, e.g. `1500000` with 6 decimals is `1.5`.
+pub fn format_amount(amount: u128, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (int, frac) = digits.split_at(digits.len() - decimals);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        int.to_string()
+    } else {
+        format!("{int}.{frac}")
+    }
+}
+
+/// Fungible tokens received through `ft_transfer_call`.
+/// Each payment is also logged with a `payment_received` event.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IncomingPayment {
+    pub token_id: AccountId,
+    pub sender_id: AccountId,
+    pub amount: U128,
+    /// `msg` of `ft_transfer_call`, such as an invoice reference.
+    pub memo: Option<String>,
+    pub timestamp: U64,
+}
+
+/// Confirmation of a request by a member.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Confirmation {
+    pub member: MultisigMember,
+    pub timestamp: U64,
+}
+
+/// Outcome of an executed request.
+#[derive(Debug, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExecutionOutcome {
+    pub success: bool,
+    pub response: FuncResponse,
+    pub executed_at: U64,
+}
+
+/// How a request left the active requests.
+#[derive(Debug, PartialEq, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum RequestStatus {
+    /// Executed, waiting for the result of its promise.
+    Executing,
+    Executed {
+        success: bool,
+    },
+    Deleted {
+        reason: DeletionReason,
+    },
+}
+
+/// Finished request kept in the contract history.
+#[derive(Debug, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HistoryEntry {
+    pub request_id: RequestId,
+    /// The request, the code of `DeployContract` is not kept.
+    pub request: MultiSigRequest,
+    pub proposer: MultisigMember,
+    pub confirmations: Vec<Confirmation>,
+    pub added_at: U64,
+    /// When the request was executed or deleted.
+    pub finished_at: U64,
+    pub status: RequestStatus,
+    /// Escrow payments created by the request.
+    pub payment_ids: Vec<Base58CryptoHash>,
+}
+
+impl HistoryEntry {
+    /// Returns members who proposed or confirmed the request.
+    pub fn members(&self) -> Vec<MultisigMember> {
+        let mut members = vec![self.proposer.clone()];
+        for confirmation in &self.confirmations {
+            if !members.contains(&confirmation.member) {
+                members.push(confirmation.member.clone());
+            }
+        }
+        members
+    }
+
+    /// Returns the payments made by the request.
+    pub fn payments(&self) -> Vec<PaymentSummary> {
+        self.request.payments()
+    }
+}
+
 #[derive(Debug, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Clone))]
 #[serde(crate = "near_sdk::serde")]